@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use ptsa_rust::utils::{
+    matrix::DistanceMatrix,
+    solution::Solution,
+    state::{State, StatesContainer},
+    tabu::TabuList,
+    temp::TemperatureBounds,
+};
+
+/// Same size as rbg358, the largest instance of the experiments
+const SIZE: usize = 358;
+const REPLICAS: usize = 40;
+
+fn random_matrix(size: usize) -> DistanceMatrix<i64> {
+    let mut rng = StdRng::seed_from_u64(358);
+    let matrix = (0..size)
+        .map(|_| (0..size).map(|_| rng.gen_range(0..100)).collect())
+        .collect();
+    DistanceMatrix::new(matrix)
+}
+
+fn metropolis(c: &mut Criterion) {
+    let dmatrix = random_matrix(SIZE);
+    let bounds = TemperatureBounds {
+        max: 50.0,
+        min: 0.1,
+    };
+    let mut group = c.benchmark_group("metropolis_rbg358");
+    group.throughput(Throughput::Elements(REPLICAS as u64));
+    for (name, temperature, is_shuffle_transition, tenure) in [
+        ("swap_cold", 0.1, false, 0),
+        ("swap_hot", 50.0, false, 0),
+        ("shuffle_cold", 0.1, true, 0),
+        ("swap_cold_tabu", 0.1, false, 20),
+    ] {
+        let mut states = StatesContainer::new(bounds.clone(), &dmatrix);
+        for _ in 0..REPLICAS {
+            let state = State {
+                solution: Solution::random_solution(SIZE),
+                is_shuffle_transition,
+                tabu: (tenure > 0).then(|| TabuList::new(tenure)),
+            };
+            states.add(state, temperature);
+        }
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| states.metropolis_tranision(0.3))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, metropolis);
+criterion_main!(benches);
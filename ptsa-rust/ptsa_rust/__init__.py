@@ -1,17 +1,27 @@
+from typing import List, Optional, Union
+
+import numpy as np
+
 from ptsa_rust.parameters import Parameters
-from ptsa_rust.result import Result
+from ptsa_rust.result import BatchResult, Result
 
 from .ptsa_rust import *
 
 
-def run_ptsa(distance_matrix: list[list[float]], time_s: int = 60, **kwargs) -> Result:
+def run_ptsa(
+    distance_matrix: Union[np.ndarray, List[List[float]], List[List[int]]],
+    time_s: int = 60,
+    preset: Optional[str] = None,
+    **kwargs,
+) -> Result:
     """
     Run the PTSA algorithm on a given distance matrix
     for specified about of time (in seconds).
 
     Any additional keyword arguments will be passed to the
-    params object `ptsa_rust.parameters.Parameters` and used by the runner.
+    params object `ptsa_rust.parameters.Parameters` and used by the runner,
+    on top of the named `preset` when one is given.
     """
-    params = Parameters(**kwargs)
+    params = Parameters.from_preset(preset, **kwargs) if preset else Parameters(**kwargs)
     runner = PtsaAlgorithm(params)
     return runner.run_for(distance_matrix, time_s)
@@ -1,4 +1,4 @@
-from dataclasses import dataclass
+from dataclasses import dataclass, field
 
 
 @dataclass
@@ -11,7 +11,55 @@ class Parameters:
     probability_of_heuristic: float = 0.5
     temp_beta_a: float = 1
     temp_beta_b: float = 1
+    # Longest mutated segment as a fraction of the tour, at most 0.5
     max_length_percent_of_cycle: float = 0.3
     swap_states_probability: float = 0.2
     closeness: float = 1.5
     cooling_rate: float = 0.95
+    # Construction heuristics seeding the replicas, any of:
+    # nearest_neighbor, backward_nearest_neighbor, greedy_edge, cheapest_insertion,
+    # nearest_insertion, farthest_insertion, random_insertion, karp_patching
+    heuristics: list[str] = field(
+        default_factory=lambda: ["nearest_neighbor", "backward_nearest_neighbor"]
+    )
+    heuristic_keep_fraction: float = 0.1  # best part of each heuristic's tours kept
+    # Instances up to this size are solved exactly (Held-Karp up to 20 cities,
+    # branch and bound above for up to half of the time limit, then the
+    # annealer), 0 disables the exact solvers
+    exact_solver_max_size: int = 20
+    # Candidate lists of the closest successors and predecessors of every city,
+    # ranked by "cost" or by "alpha" (reduced costs of the assignment relaxation)
+    number_of_neighbours: int = 8
+    neighbour_ranking: str = "cost"
+    # Local search (Or-opt and reversal-free 3-opt) over the candidate lists
+    polish_final: bool = True  # polish the returned tour
+    local_search_period: int = 0  # polish each thread's best replica every N coolings, 0 = never
+    # Derive number_of_states, the temperature bounds, max_length_percent_of_cycle
+    # and cooling_rate from the instance; the chosen values are in result["parameters"]
+    auto: bool = False
+    # Every N coolings two of the cheaper replicas of a thread are crossed and the
+    # offspring replaces the costliest of the hotter half, 0 = never; the operator
+    # is drawn from order, edge_recombination and edge_assembly (EAX)
+    crossover_period: int = 0
+    crossovers: list[str] = field(
+        default_factory=lambda: ["order", "edge_recombination", "edge_assembly"]
+    )
+    # Proposals for which a replica may not change back the arcs of an accepted
+    # move, unless it beats the best cost; 0 disables the tabu lists
+    tabu_tenure: int = 0
+    # Time windows and capacities: lateness and overload cost penalty_weight per
+    # unit on top of the distance; after every cooling the weight grows by the
+    # factor 1 + penalty_adaptation while fewer than half of the replicas are feasible
+    # and shrinks by it otherwise, staying within 1000 times the initial weight
+    penalty_weight: float = 1
+    penalty_adaptation: float = 0.05
+    # Generalized TSPs: share of the proposals that visit a cluster at another
+    # of its cities instead of changing the cluster order
+    probability_of_choice: float = 0.3
+
+    @classmethod
+    def from_preset(cls, name: str, **overrides) -> "Parameters":
+        """Named settings shared with the Rust API and the CLI: default, small or large"""
+        from ptsa_rust.ptsa_rust import preset
+
+        return cls(**{**preset(name), **overrides})
@@ -1,14 +1,82 @@
+import numpy as np
 from parameters import Parameters
-from result import Result
+from result import BatchResult, Result
+
+class PtsaError(ValueError):
+    """Base class of the errors raised by the solver."""
+
+class InvalidMatrixError(PtsaError):
+    """The distance matrix is not square, too small or has NaN, negative or infinite entries."""
+
+class InvalidParametersError(PtsaError):
+    """A parameter is out of its allowed range."""
+
+def preset(name: str) -> dict:
+    """
+    Fields of the named `Parameters` preset: "default", "small"
+    (instances below 300 cities) or "large". The small and large presets
+    have the same fields and differ only in the time limit that config
+    files and the CLI take from them.
+    Raises `InvalidParametersError` for unknown names.
+    """
+    ...
 
 class PtsaAlgorithm:
     # HACK: This is not true, but will work just fine
     params: Parameters
 
-    def __init__(self, parms: Parameters) -> None: ...
-    def run_for(self, matrix: list[list[float]], time: int) -> Result:
+    def __init__(self, parms: Parameters) -> None:
+        """Raises `InvalidParametersError` for out of range parameters."""
+        ...
+    def run_for(
+        self,
+        matrix: np.ndarray | list[list[float]] | list[list[int]],
+        time: int,
+        open: bool = False,
+        start: int | None = None,
+        end: int | None = None,
+        time_windows: list[tuple[float, float, float]] | None = None,
+        vehicles: int | None = None,
+        depot: int = 0,
+        capacity: float | None = None,
+        demands: list[float] | None = None,
+        clusters: list[list[int]] | None = None,
+    ) -> Result:
         """
         Run the PTSA algorithm on a given distance matrix
-        for specified about of time (in seconds)
+        for specified about of time (in seconds).
+        With `open`, a `start` or an `end` city the tour is a Hamiltonian
+        path without the arc back, starting at `start` when it is set.
+        `time_windows` gives every city a `(ready, due, service)` window,
+        travelling an arc takes its cost in time and the tour leaves city 0
+        (of a cycle) or its first city at time 0; arriving early waits and
+        lateness is penalized, see `result["lateness"]`.
+        With `vehicles` the cities are split into up to that many routes
+        from and back to the `depot`, see `result["routes"]`; a route whose
+        `demands` add up to more than the `capacity` is penalized like
+        lateness. The solution then lists the depot before every route.
+        With `clusters`, lists of cities covering each city once, the tour
+        visits one city of every cluster and the solution lists those.
+        Integer matrices are solved with exact integer arithmetic.
+        C-contiguous float64, float32, int64 and int32 arrays are read
+        directly from their buffer, anything else goes through lists.
+        Raises `InvalidMatrixError` for unusable matrices.
+        """
+        ...
+    def solve_many(
+        self,
+        problems: list[np.ndarray | list[list[float]] | list[list[int]] | str],
+        time_each: float,
+        runs_each: int = 1,
+        jobs: int | None = None,
+    ) -> list[BatchResult]:
+        """
+        Run every problem, a distance matrix or the path of a TSPLIB
+        (TSP, ATSP or SOP) or `.csv` file, `runs_each` times for `time_each` seconds each.
+        The runs are spread over `jobs` threads (by default as many as the
+        solver threads leave room for) and the runs of a problem share its
+        heuristic tours and candidate lists.
+        Costs are integers when every problem has whole distances.
+        Raises `InvalidMatrixError` for unusable matrices and files.
         """
         ...
@@ -1,6 +1,29 @@
 from typing import TypedDict
 
 
+class TabuStats(TypedDict):
+    checked: int  # proposals checked against a tabu list
+    blocked: int  # rejected for changing back a recent move
+    aspirated: int  # let through for beating the best cost
+
+
 class Result(TypedDict):
     solution: list[int]
-    cost: float
+    cost: float  # an int for integer matrices
+    lower_bound: float  # assignment problem relaxation
+    gap: float  # (cost - lower_bound) / lower_bound in percent, inf for a zero bound under a positive cost
+    local_search_improvement: float  # cost removed by the local search
+    time_to_best: float  # seconds until the search found the tour
+    runtime: float  # seconds of the whole run
+    parameters: dict  # Parameters fields the search ran with
+    tabu: TabuStats  # all zero when tabu_tenure is 0
+    feasible: bool  # the tour keeps its fixed ends, the precedences of SOP files, the time windows and the capacity
+    lateness: list[float]  # how late the tour reaches each city, empty without time windows
+    routes: list[list[int]]  # cities of every vehicle without the depot, empty without vehicles
+    loads: list[float]  # total demand of every route
+
+
+class BatchResult(TypedDict):
+    name: str  # the file's instance name, or the position of the matrix
+    best: Result  # the run with the cheapest tour, feasible ones first
+    runs: list[Result]
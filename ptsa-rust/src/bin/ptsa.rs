@@ -0,0 +1,649 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand};
+use ptsa_rust::{
+    utils::{
+        benchmark::{Benchmark, TableFormat},
+        crossover::Crossover,
+        fleet::Fleet,
+        heuristics::Heuristic,
+        instance::{read_demands, read_optima, read_time_windows, Instance, KnownInstance},
+        neighbours::NeighbourRanking,
+        time_window::TimeWindows,
+        tour::TourKind,
+        tuner::{Method, SearchSpace, Tuner},
+    },
+    Config, Cost, Params, Preset, RunSummary, Solver, Stop,
+};
+
+/// Time limit used when neither the flags nor the config give any limit
+const DEFAULT_TIME_S: f64 = 60.0;
+
+/// Extensions of the instance files picked from a directory
+const INSTANCE_EXTENSIONS: [&str; 5] = ["atsp", "tsp", "sop", "gtsp", "csv"];
+
+/// Solves an asymmetric TSP instance with parallel tempering simulated annealing
+#[derive(Parser, Debug)]
+#[command(
+    name = "ptsa",
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// TSPLIB file (TSP, ATSP or SOP), or a matrix in a `.csv` file
+    #[arg(required = true)]
+    instance: Option<PathBuf>,
+
+    /// TOML, JSON or YAML file with parameters and stopping criteria,
+    /// flags given on the command line take precedence
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Named parameter set: default, small or large
+    #[arg(long, conflicts_with = "config")]
+    preset: Option<Preset>,
+
+    /// Known optimal cost, reported as the gap in the summary
+    #[arg(long)]
+    optimum: Option<f64>,
+
+    /// Where to write the tour: JSON for `.json`, TSPLIB `.tour` otherwise
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print the progress of the search
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Look for a Hamiltonian path instead of a cycle
+    #[arg(long)]
+    open: bool,
+
+    /// City the path starts at, 0-based, implies --open
+    #[arg(long)]
+    start: Option<usize>,
+
+    /// City the path ends at, 0-based, implies --open
+    #[arg(long)]
+    end: Option<usize>,
+
+    /// Time windows of the cities, `ready,due,service` lines in city order;
+    /// arcs take their cost in time and lateness is penalized
+    #[arg(long)]
+    time_windows: Option<PathBuf>,
+
+    /// Split the tour into routes of up to this many vehicles leaving the depot
+    #[arg(long)]
+    vehicles: Option<usize>,
+
+    /// City the vehicles leave from and come back to, 0-based
+    #[arg(long, default_value_t = 0, requires = "vehicles")]
+    depot: usize,
+
+    /// Most demand a vehicle carries, overloads are penalized like lateness
+    #[arg(long, requires_all = ["vehicles", "demands"])]
+    capacity: Option<f64>,
+
+    /// Demands of the cities, one number per line in city order
+    #[arg(long, requires = "capacity")]
+    demands: Option<PathBuf>,
+
+    #[command(flatten)]
+    stop: StopFlags,
+
+    #[command(flatten)]
+    params: ParamFlags,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Searches the parameters with the lowest deficit on instances with known optima
+    Tune(TuneArgs),
+    /// Runs instances with known optima repeatedly and tabulates the results
+    Bench(BenchArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    /// Instance files, or directories of `.atsp`, `.tsp`, `.sop` and `.csv` files
+    #[arg(required = true)]
+    instances: Vec<PathBuf>,
+
+    /// Best known costs, `name,cost` lines
+    #[arg(long)]
+    optima: PathBuf,
+
+    /// TOML, JSON or YAML file with parameters and stopping criteria
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Named parameter set: default, small or large
+    #[arg(long, conflicts_with = "config")]
+    preset: Option<Preset>,
+
+    /// Runs of every instance
+    #[arg(long, default_value_t = 10)]
+    runs: usize,
+
+    /// Seed of the first run, the next runs count up from it
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Time limit of each run in seconds
+    #[arg(long, value_parser = seconds)]
+    time: Option<Duration>,
+
+    /// Iterations limit of each run
+    #[arg(long)]
+    iterations: Option<usize>,
+
+    /// Where to write the table: `.csv` (every run), `.md` or `.tex`,
+    /// can be repeated; a Markdown table is printed without it
+    #[arg(short, long)]
+    output: Vec<PathBuf>,
+
+    /// Print every finished run
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct TuneArgs {
+    /// Instance files, or directories of `.atsp`, `.tsp`, `.sop` and `.csv` files
+    #[arg(required = true)]
+    instances: Vec<PathBuf>,
+
+    /// Best known costs, `name,cost` lines
+    #[arg(long)]
+    optima: PathBuf,
+
+    /// Tuned fields: `field = { min, max }` ranges and `field = [..]` choices
+    #[arg(long)]
+    space: PathBuf,
+
+    /// Config whose parameters are tuned, the defaults otherwise
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Named parameter set the tuning starts from
+    #[arg(long, conflicts_with = "config")]
+    preset: Option<Preset>,
+
+    /// `race` (F-race) or `halving` (successive halving)
+    #[arg(long, default_value_t = Method::Race)]
+    method: Method,
+
+    /// Configurations drawn, the starting parameters among them
+    #[arg(long, default_value_t = 16)]
+    candidates: usize,
+
+    /// Solver runs in total
+    #[arg(long, default_value_t = 200)]
+    budget: usize,
+
+    /// Runs at the same time, by default as many as the threads of the
+    /// starting parameters leave room for
+    #[arg(long)]
+    jobs: Option<usize>,
+
+    /// Time limit of each run in seconds
+    #[arg(long, value_parser = seconds, default_value = "10")]
+    time: Duration,
+
+    /// Iterations limit of each run
+    #[arg(long)]
+    iterations: Option<usize>,
+
+    /// Seed of the candidates drawn and of the runs, which are random without it
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Where to write the best parameters: TOML, JSON or YAML by the extension
+    #[arg(long)]
+    best: Option<PathBuf>,
+
+    /// Where to write every evaluated configuration, ranked, as CSV
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print the progress of the rounds
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct StopFlags {
+    /// Time limit in seconds
+    #[arg(long, value_parser = seconds)]
+    time: Option<Duration>,
+    /// Iterations (cooling steps) of every thread
+    #[arg(long)]
+    iterations: Option<usize>,
+    /// Stop once a tour this cheap is found
+    #[arg(long)]
+    target_cost: Option<f64>,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(next_help_heading = "Parameters")]
+struct ParamFlags {
+    /// Replicas of every thread
+    #[arg(long)]
+    number_of_states: Option<usize>,
+    /// Threads, also metropolis sweeps per cooling
+    #[arg(long)]
+    number_of_repeats: Option<usize>,
+    /// Floor of the cooling
+    #[arg(long)]
+    min_temperature: Option<f64>,
+    /// Hottest temperature, gives the longest moves
+    #[arg(long)]
+    max_temperature: Option<f64>,
+    /// Share of replicas shuffling segments instead of swapping them
+    #[arg(long)]
+    probability_of_shuffle: Option<f64>,
+    /// Share of replicas starting from a heuristic tour
+    #[arg(long)]
+    probability_of_heuristic: Option<f64>,
+    /// Beta distribution shape of the initial temperatures
+    #[arg(long)]
+    temp_beta_a: Option<f64>,
+    /// Beta distribution shape of the initial temperatures
+    #[arg(long)]
+    temp_beta_b: Option<f64>,
+    /// Longest mutated segment as a fraction of the tour, at most 0.5
+    #[arg(long)]
+    max_length_percent_of_cycle: Option<f64>,
+    /// Probability of exchanging the temperatures of two replicas
+    #[arg(long)]
+    swap_states_probability: Option<f64>,
+    /// Replicas cheaper than closeness * best cost keep their temperature
+    #[arg(long)]
+    closeness: Option<f64>,
+    /// Temperature multiplier of every cooling step
+    #[arg(long)]
+    cooling_rate: Option<f64>,
+    /// Heuristics seeding the replicas, comma separated,
+    /// e.g. `nearest_neighbor,greedy_edge`
+    #[arg(long, value_delimiter = ',')]
+    heuristics: Option<Vec<Heuristic>>,
+    /// Best part of each heuristic's tours kept
+    #[arg(long)]
+    heuristic_keep_fraction: Option<f64>,
+    /// Instances up to this size are solved exactly, 0 disables it
+    #[arg(long)]
+    exact_solver_max_size: Option<usize>,
+    /// Length of the candidate lists
+    #[arg(long)]
+    number_of_neighbours: Option<usize>,
+    /// Ranking of the candidate lists: `cost` or `alpha`
+    #[arg(long)]
+    neighbour_ranking: Option<NeighbourRanking>,
+    /// Polish the returned tour with the local search
+    #[arg(long)]
+    polish_final: Option<bool>,
+    /// Polish each thread's best replica every N coolings, 0 = never
+    #[arg(long)]
+    local_search_period: Option<usize>,
+    /// Derive states, temperatures, segment length and cooling from the instance
+    #[arg(long)]
+    auto: Option<bool>,
+    /// Cross two cheap replicas every N coolings, 0 = never
+    #[arg(long)]
+    crossover_period: Option<usize>,
+    /// Crossovers drawn from, comma separated: `order`, `edge_recombination`,
+    /// `edge_assembly`
+    #[arg(long, value_delimiter = ',')]
+    crossovers: Option<Vec<Crossover>>,
+    /// Proposals for which a replica may not change back the arcs of an
+    /// accepted move, 0 disables the tabu lists
+    #[arg(long)]
+    tabu_tenure: Option<usize>,
+    /// Cost of a unit of lateness or overload, where the adaptation starts from
+    #[arg(long)]
+    penalty_weight: Option<f64>,
+    /// Relative change of the penalty weight after every cooling, 0 keeps it
+    #[arg(long)]
+    penalty_adaptation: Option<f64>,
+    /// Share of the proposals choosing another city of a cluster
+    #[arg(long)]
+    probability_of_choice: Option<f64>,
+}
+
+macro_rules! override_params {
+    ($params:expr, $flags:expr, $($field:ident),*) => {
+        $(
+            if let Some(value) = $flags.$field.clone() {
+                $params.$field = value;
+            }
+        )*
+    };
+}
+
+impl Args {
+    fn params(&self, mut params: Params) -> Params {
+        override_params!(
+            params,
+            self.params,
+            number_of_states,
+            number_of_repeats,
+            min_temperature,
+            max_temperature,
+            probability_of_shuffle,
+            probability_of_heuristic,
+            temp_beta_a,
+            temp_beta_b,
+            max_length_percent_of_cycle,
+            swap_states_probability,
+            closeness,
+            cooling_rate,
+            heuristics,
+            heuristic_keep_fraction,
+            exact_solver_max_size,
+            number_of_neighbours,
+            neighbour_ranking,
+            polish_final,
+            local_search_period,
+            auto,
+            crossover_period,
+            crossovers,
+            tabu_tenure,
+            penalty_weight,
+            penalty_adaptation,
+            probability_of_choice
+        );
+        params
+    }
+
+    fn stop(&self, config: &Stop) -> Stop {
+        let time = self.stop.time.or(config.time);
+        let iterations = self.stop.iterations.or(config.iterations);
+        let time = match (time, iterations) {
+            (None, None) => Some(Duration::from_secs_f64(DEFAULT_TIME_S)),
+            _ => time,
+        };
+        Stop {
+            time,
+            iterations,
+            target_cost: self.stop.target_cost.or(config.target_cost),
+        }
+    }
+}
+
+/// A time limit in seconds, which has to be finite and non-negative
+fn seconds(text: &str) -> Result<Duration, String> {
+    let seconds = text.parse::<f64>().map_err(|e| e.to_string())?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("{} is not a duration", seconds));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+fn write_tour<C: Cost>(
+    path: &PathBuf,
+    name: &str,
+    summary: &RunSummary<C>,
+) -> Result<(), Box<dyn Error>> {
+    let path_cities = &summary.best.solution.path;
+    let text = if path.extension().and_then(|extension| extension.to_str()) == Some("json") {
+        let json = serde_json::json!({
+            "name": name,
+            "cost": summary.best.cost.to_f64(),
+            "tour": path_cities,
+            "lower_bound": summary.lower_bound,
+            "runtime_s": summary.elapsed.as_secs_f64(),
+            "params": summary.params,
+            "tabu": summary.tabu,
+            "feasible": summary.feasible,
+            "lateness": summary.lateness,
+            "routes": summary.routes,
+            "loads": summary.loads,
+        });
+        serde_json::to_string_pretty(&json)?
+    } else {
+        // TSPLIB tours are 1-based and end with -1
+        let mut text = format!(
+            "NAME : {}.tour\nCOMMENT : Length = {}\nTYPE : TOUR\nDIMENSION : {}\nTOUR_SECTION\n",
+            name,
+            summary.best.cost,
+            path_cities.len()
+        );
+        for city in path_cities {
+            text.push_str(&format!("{}\n", city + 1));
+        }
+        text.push_str("-1\nEOF\n");
+        text
+    };
+    fs::write(path, text)?;
+    Ok(())
+}
+
+fn solve<C: Cost>(args: &Args, instance: &Instance, solver: &Solver) -> Result<(), Box<dyn Error>> {
+    // The flags override the path of a sequential ordering instance
+    let kind = match TourKind::new(args.open, args.start, args.end) {
+        TourKind::Cycle => instance.kind,
+        kind => kind,
+    };
+    let mut dmatrix = instance.matrix::<C>().with_kind(kind);
+    if let Some(path) = &args.time_windows {
+        dmatrix = dmatrix.with_time_windows(TimeWindows::new(read_time_windows(path)?));
+    }
+    if let Some(vehicles) = args.vehicles {
+        let mut fleet = Fleet::new(vehicles, args.depot);
+        if let (Some(capacity), Some(path)) = (args.capacity, &args.demands) {
+            fleet = fleet.with_capacity(capacity, read_demands(path)?);
+        }
+        dmatrix = dmatrix.with_fleet(fleet);
+    }
+    let summary = solver.solve(&dmatrix)?;
+    if let Some(path) = &args.output {
+        write_tour(path, &instance.name, &summary)?;
+    }
+    let cost = summary.best.cost;
+    let gap = match args.optimum {
+        Some(optimum) => format!("optimum={} gap={:.2}%", optimum, summary.best.gap(optimum)),
+        None => format!(
+            "lower_bound={} gap={:.2}%",
+            summary.lower_bound,
+            summary.best.gap(summary.lower_bound)
+        ),
+    };
+    println!(
+        "{} cost={} {} time={:.2}s",
+        instance.name,
+        cost,
+        gap,
+        summary.elapsed.as_secs_f64()
+    );
+    if !summary.feasible {
+        println!(
+            "the tour breaks the fixed ends, precedences, time windows or capacities of the instance"
+        );
+    }
+    let late: Vec<String> = summary
+        .lateness
+        .iter()
+        .enumerate()
+        .filter(|&(_, &late)| late > 0.0)
+        .map(|(city, late)| format!("{}:{}", city, late))
+        .collect();
+    if !late.is_empty() {
+        println!("late cities {}", late.join(" "));
+    }
+    for (vehicle, (route, load)) in summary.routes.iter().zip(&summary.loads).enumerate() {
+        let cities: Vec<String> = route.iter().map(|city| city.to_string()).collect();
+        println!("route {} load={} {}", vehicle, load, cities.join(" "));
+    }
+    if summary.tabu.checked > 0 {
+        println!(
+            "tabu blocked={} ({:.2}%) aspirated={}",
+            summary.tabu.blocked,
+            100.0 * summary.tabu.blocked_ratio(),
+            summary.tabu.aspirated
+        );
+    }
+    Ok(())
+}
+
+/// The files, with directories replaced by the instance files inside them
+fn instance_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = vec![];
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut inside: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        inside.retain(|file| {
+            file.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| INSTANCE_EXTENSIONS.contains(&extension))
+        });
+        inside.sort();
+        files.extend(inside);
+    }
+    Ok(files)
+}
+
+fn known_instances(paths: &[PathBuf], optima: &Path) -> Result<Vec<KnownInstance>, Box<dyn Error>> {
+    let optima = read_optima(optima)?;
+    instance_files(paths)?
+        .into_iter()
+        .map(|path| {
+            let instance = Instance::read(&path)?;
+            let optimum = *optima
+                .get(&instance.name)
+                .ok_or_else(|| format!("no known optimum of {}", instance.name))?;
+            Ok(KnownInstance { instance, optimum })
+        })
+        .collect()
+}
+
+/// The `--config` file, the `--preset`, or the defaults without a time limit
+fn read_config(path: &Option<PathBuf>, preset: Option<Preset>) -> Result<Config, Box<dyn Error>> {
+    Ok(match (path, preset) {
+        (Some(path), _) => Config::read(path)?,
+        (None, Some(preset)) => Config {
+            params: preset.params(),
+            stop: Stop::after(preset.time()),
+        },
+        (None, None) => Config {
+            params: Params::default(),
+            stop: Stop::default(),
+        },
+    })
+}
+
+fn tune(args: TuneArgs) -> Result<(), Box<dyn Error>> {
+    let base = read_config(&args.config, args.preset)?.params;
+    let jobs = args.jobs.unwrap_or_else(|| {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        usize::max(threads / base.number_of_repeats, 1)
+    });
+    let mut stop = Stop::after(args.time);
+    stop.iterations = args.iterations;
+    let tuner = Tuner {
+        space: SearchSpace::read(&args.space)?,
+        base,
+        stop,
+        method: args.method,
+        candidates: args.candidates,
+        budget: args.budget,
+        jobs,
+        seed: args.seed,
+        verbose: args.verbose,
+    };
+
+    let instances = known_instances(&args.instances, &args.optima)?;
+    let tuning = tuner.tune(&instances)?;
+    if let Some(path) = &args.output {
+        fs::write(path, tuning.to_csv())?;
+    }
+    let best = tuning.best();
+    if let Some(path) = &args.best {
+        best.params.save(path)?;
+    }
+    println!(
+        "best #{} mean_deficit={:.3}% evaluations={} runs={}",
+        best.id,
+        best.mean_deficit(),
+        best.deficits.len(),
+        tuning.runs
+    );
+    Ok(())
+}
+
+fn bench(args: BenchArgs) -> Result<(), Box<dyn Error>> {
+    let config = read_config(&args.config, args.preset)?;
+    let time = args.time.or(config.stop.time);
+    let iterations = args.iterations.or(config.stop.iterations);
+    let stop = Stop {
+        time: match (time, iterations) {
+            (None, None) => Some(Duration::from_secs_f64(DEFAULT_TIME_S)),
+            _ => time,
+        },
+        iterations,
+        target_cost: None,
+    };
+    let benchmark = Benchmark {
+        solver: Solver::builder().params(config.params).stop(stop).build()?,
+        runs: args.runs,
+        seed: args.seed,
+        verbose: args.verbose,
+    };
+
+    let report = benchmark.run(&known_instances(&args.instances, &args.optima)?)?;
+    if args.output.is_empty() {
+        print!("{}", report.render(TableFormat::Markdown));
+    }
+    for path in args.output.iter() {
+        fs::write(path, report.render(TableFormat::of(path)))?;
+    }
+    Ok(())
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let config = read_config(&args.config, args.preset)?;
+    let solver = Solver::builder()
+        .params(args.params(config.params.clone()))
+        .stop(args.stop(&config.stop))
+        .verbose(args.verbose)
+        .build()?;
+
+    let instance = Instance::read(args.instance.as_ref().expect("Required without a command"))?;
+    // Whole-number instances keep exact integer costs
+    if instance.is_integral() {
+        solve::<i64>(&args, &instance, &solver)
+    } else {
+        solve::<f64>(&args, &instance, &solver)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let result = match args.command {
+        Some(Command::Tune(tune_args)) => tune(tune_args),
+        Some(Command::Bench(bench_args)) => bench(bench_args),
+        None => run(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("ptsa: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,215 +1,345 @@
-use chrono::Utc;
-use pyo3::prelude::*;
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use std::{
-    f64::INFINITY,
-    sync::{Arc, Mutex},
-    thread::{self, ScopedJoinHandle},
+// pyo3 0.19 macros expand to impl blocks inside generated functions
+#![allow(non_local_definitions)]
+#[cfg(feature = "python")]
+use numpy::{Element, PyReadonlyArray2};
+#[cfg(feature = "python")]
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyList},
 };
+#[cfg(feature = "python")]
+use std::{path::PathBuf, time::Duration};
+#[cfg(feature = "python")]
 use utils::{
+    array::matrix_from_array,
+    batch::{Batch, BatchResult},
+    cluster::Clusters,
+    fleet::Fleet,
+    instance::Instance,
+    time_window::{TimeWindow, TimeWindows},
+    tour::TourKind,
+    validation::{InvalidMatrixError, InvalidParametersError, PtsaError},
+};
+pub mod utils;
+
+pub use utils::{
+    config::{Config, Preset},
+    cost::Cost,
     matrix::DistanceMatrix,
     params::Params,
     solution::{ComputedSolution, Solution},
-    state::{State, StatesContainer},
-    temp::TemperatureBounds,
+    solver::{RunSummary, Solver, SolverBuilder, Stop},
+    validation::ValidationError,
 };
-mod utils;
 
+#[cfg(feature = "python")]
 #[pyclass]
 pub struct PtsaAlgorithm {
     pub params: Params,
 }
 
+#[cfg(feature = "python")]
+#[pymethods]
 impl PtsaAlgorithm {
-    #[inline]
-    fn get_best_heuristic_solutions(
-        &self,
-        dmatrix: &DistanceMatrix,
-        backward: bool,
-    ) -> Vec<Solution> {
-        let create_heuristic_solution = if backward {
-            Solution::backwards_nearest_neightbor_solution
-        } else {
-            Solution::nearest_neightbor_solution
-        };
-        let mut all_heuristic_solutions: Vec<(Solution, f64)> = (0..dmatrix.size)
-            .map(|starting_city| create_heuristic_solution(dmatrix, starting_city))
-            .map(|solution| {
-                let cost = solution.cost(dmatrix);
-                (solution, cost)
-            })
-            .collect();
-        all_heuristic_solutions.sort_by(|(_, a), (_, b)| a.total_cmp(b));
-        let takes = (all_heuristic_solutions.len() as f64 * 0.10) as usize;
-        all_heuristic_solutions
-            .into_iter()
-            .take(takes)
-            .map(|(solution, _)| solution)
-            .collect()
+    #[new]
+    pub fn new(parameters: PyObject) -> PyResult<Self> {
+        Python::with_gil(|py| {
+            let params: Params = parameters.extract(py)?;
+            params.validate()?;
+            Ok(PtsaAlgorithm { params })
+        })
     }
 
-    #[inline]
-    fn init_states<'a>(
+    #[pyo3(signature = (
+        matrix,
+        time,
+        open = false,
+        start = None,
+        end = None,
+        time_windows = None,
+        vehicles = None,
+        depot = 0,
+        capacity = None,
+        demands = None,
+        clusters = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn run_for(
         &self,
-        distance_matrix: &'a DistanceMatrix,
-        starting_solutions: Vec<Solution>,
-    ) -> StatesContainer<'a> {
-        // Initialization
-        let problem_size = distance_matrix.size;
-        for solution in starting_solutions.iter() {
-            assert_eq!(solution.size, problem_size);
-        }
-        let temp_bounds = TemperatureBounds {
-            max: self.params.max_temperature,
-            min: self.params.min_temperature,
+        py: Python,
+        matrix: &PyAny,
+        time: i64,
+        open: bool,
+        start: Option<usize>,
+        end: Option<usize>,
+        time_windows: Option<Vec<(f64, f64, f64)>>,
+        vehicles: Option<usize>,
+        depot: usize,
+        capacity: Option<f64>,
+        demands: Option<Vec<f64>>,
+        clusters: Option<Vec<Vec<usize>>>,
+    ) -> PyResult<PyObject> {
+        // Run the PTSA algorithm on a given distance matrix
+        // for specified about of time (in seconds).
+        // Integer matrices are kept as integers, so the costs stay exact.
+        let problem = Problem {
+            kind: TourKind::new(open, start, end),
+            time_windows: time_windows.map(|windows| {
+                let windows = windows
+                    .into_iter()
+                    .map(|(ready, due, service)| TimeWindow {
+                        ready,
+                        due,
+                        service,
+                    })
+                    .collect();
+                TimeWindows::new(windows)
+            }),
+            fleet: vehicles.map(|vehicles| Fleet {
+                vehicles,
+                depot,
+                capacity,
+                demands: demands.unwrap_or_default(),
+            }),
+            clusters: clusters.map(Clusters::new),
         };
-        // Creating states
-        let mut states: StatesContainer =
-            StatesContainer::new(temp_bounds.clone(), distance_matrix);
-        for solution in starting_solutions.into_iter() {
-            let temperature =
-                temp_bounds.random_temperature(self.params.temp_beta_a, self.params.temp_beta_b);
-            let is_shuffle_transition = rand::random::<f64>() < self.params.probability_of_shuffle;
-            let state = State {
-                solution,
-                temperature,
-                is_shuffle_transition,
-            };
-            states.add(state);
+        if let Some(result) = self.try_run_array::<f64>(py, matrix, time, &problem)? {
+            return Ok(result);
+        }
+        if let Some(result) = self.try_run_array::<f32>(py, matrix, time, &problem)? {
+            return Ok(result);
         }
-        states
+        if let Some(result) = self.try_run_array::<i64>(py, matrix, time, &problem)? {
+            return Ok(result);
+        }
+        if let Some(result) = self.try_run_array::<i32>(py, matrix, time, &problem)? {
+            return Ok(result);
+        }
+        // Nested lists and any other array
+        if let Ok(matrix) = matrix.extract::<Vec<Vec<i64>>>() {
+            let dmatrix = problem.attach(DistanceMatrix::try_new(matrix)?);
+            return self.run_into_dict(py, dmatrix, time);
+        }
+        let matrix: Vec<Vec<f64>> = matrix.extract()?;
+        self.run_into_dict(py, problem.attach(DistanceMatrix::try_new(matrix)?), time)
     }
 
-    fn create_inital_states<'a>(
+    /// Runs every problem, a matrix or a TSPLIB/CSV file path, `runs_each`
+    /// times for `time_each` seconds, `jobs` runs at a time. The runs of a
+    /// problem share its candidate lists and heuristic tours.
+    #[pyo3(signature = (problems, time_each, runs_each = 1, jobs = None))]
+    pub fn solve_many(
         &self,
-        n: usize,
-        distance_matrix: &'a DistanceMatrix,
-        heuristic_solutions: &Vec<Solution>,
-    ) -> StatesContainer<'a> {
-        let rng = &mut thread_rng();
-        let solutions: Vec<Solution> = (0..n)
-            .map(|_| {
-                let take_heuristic = rng.gen_range(0.0..1.0) < self.params.probability_of_heuristic;
-                if take_heuristic {
-                    heuristic_solutions.choose(rng).unwrap().clone()
-                } else {
-                    Solution::random_solution(distance_matrix.size)
-                }
-            })
-            .collect();
-        // TODO: Make it just an iterator. Do not collect into vector
-        self.init_states(distance_matrix, solutions)
+        py: Python,
+        problems: &PyList,
+        time_each: f64,
+        runs_each: usize,
+        jobs: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let instances = problems
+            .iter()
+            .enumerate()
+            .map(|(index, problem)| problem_instance(index, problem))
+            .collect::<PyResult<Vec<Instance>>>()?;
+        if !time_each.is_finite() || time_each < 0.0 {
+            return Err(InvalidParametersError::new_err(format!(
+                "time_each {} is not a duration",
+                time_each
+            )));
+        }
+        let solver = Solver::builder()
+            .params(self.params.clone())
+            .stop(Stop::after(Duration::from_secs_f64(time_each)))
+            .build()?;
+        let batch = Batch {
+            jobs: jobs.unwrap_or_else(|| Batch::default_jobs(&solver)),
+            solver,
+            runs: runs_each,
+        };
+
+        // Integer costs stay exact when every problem has whole distances
+        if instances.iter().all(|instance| instance.is_integral()) {
+            let matrices: Vec<DistanceMatrix<i64>> =
+                instances.iter().map(|instance| instance.matrix()).collect();
+            let results = py.allow_threads(|| batch.solve(&matrices))?;
+            batch_list(py, &instances, &results)
+        } else {
+            let matrices: Vec<DistanceMatrix<f64>> =
+                instances.iter().map(|instance| instance.matrix()).collect();
+            let results = py.allow_threads(|| batch.solve(&matrices))?;
+            batch_list(py, &instances, &results)
+        }
     }
+}
 
-    fn run_thread(
+#[cfg(feature = "python")]
+impl PtsaAlgorithm {
+    /// Runs on a C-contiguous NumPy array of the given dtype,
+    /// `None` when the input is anything else
+    fn try_run_array<C: Cost + Element + ToPyObject>(
         &self,
-        mut states: StatesContainer,
+        py: Python,
+        matrix: &PyAny,
         time: i64,
-        global_best: Arc<Mutex<f64>>,
-        thead_id: usize,
-    ) -> ComputedSolution {
-        let deadline = Utc::now().timestamp() + time;
-        // Main loop
-        loop {
-            // Break condition
-            if Utc::now().timestamp() >= deadline {
-                return ComputedSolution {
-                    solution: states.best_solution.unwrap(),
-                    cost: states.best_cost,
-                };
-            }
-            // Metropolis and replica transitions
-            for _ in 0..self.params.number_of_repeats {
-                states.metropolis_tranision(self.params.max_length_percent_of_cycle);
-                for _ in 0..states.states.len() {
-                    states.replica_transition(
-                        self.params.swap_states_probability,
-                        self.params.closeness,
-                    )
-                }
-            }
-            // Cooling
-            states.cool(self.params.cooling_rate);
-
-            // Update global best
-            {
-                let mut global_best_cost = global_best.lock().unwrap();
-                if states.best_cost < *global_best_cost {
-                    *global_best_cost = states.best_cost;
-                    println!("{} -> {} -> {}", Utc::now(), thead_id, states.best_cost)
-                }
-            }
+        problem: &Problem,
+    ) -> PyResult<Option<PyObject>> {
+        let array = match matrix.extract::<PyReadonlyArray2<C>>() {
+            Ok(array) => array,
+            Err(_) => return Ok(None),
+        };
+        match matrix_from_array(&array)? {
+            Some(dmatrix) => self
+                .run_into_dict(py, problem.attach(dmatrix), time)
+                .map(Some),
+            None => Ok(None),
         }
     }
 
-    fn run(&self, dmatrix: DistanceMatrix, time: i64) -> ComputedSolution {
-        let mut heuristic_solutions = self.get_best_heuristic_solutions(&dmatrix, false);
-        {
-            // This is  here to empty memory faster from second vector
-            let mut backward_heuristic_solutions =
-                self.get_best_heuristic_solutions(&dmatrix, true);
-            heuristic_solutions.append(&mut backward_heuristic_solutions);
-        }
+    fn run_into_dict<C: Cost + ToPyObject>(
+        &self,
+        py: Python,
+        dmatrix: DistanceMatrix<C>,
+        time: i64,
+    ) -> PyResult<PyObject> {
+        let solver = Solver::builder()
+            .params(self.params.clone())
+            .stop(Stop::after(Duration::from_secs(time.max(0) as u64)))
+            .verbose(true)
+            .build()?;
 
-        // Just do one run of it
-        println!("Starting SEARCH part");
-        let n = self.params.number_of_repeats;
-
-        let global_best = Arc::new(Mutex::new(INFINITY));
-        let mut results: Vec<ComputedSolution> = thread::scope(|s| {
-            let handlers: Vec<ScopedJoinHandle<'_, ComputedSolution>> = (0..n)
-                .map(|i| {
-                    println!("Starting thread number {}.", i);
-                    let initial_states = self.create_inital_states(
-                        self.params.number_of_states,
-                        &dmatrix,
-                        &heuristic_solutions,
-                    );
-                    let thead_global_best = Arc::clone(&global_best);
-                    s.spawn(move || self.run_thread(initial_states, time, thead_global_best, i))
-                })
-                .collect();
-            handlers
-                .into_iter()
-                .map(|handle| handle.join().unwrap())
-                .collect()
-        });
-        results.sort_by(|a, b| a.cost.total_cmp(&b.cost));
-        let best_solution = results.get(0).unwrap().clone();
-        println!("Finished searching for solutions.");
+        println!("Rust solver. Start!");
+        println!("See you in {} seconds!", time);
+        let summary = solver.solve(&dmatrix)?;
+        let best_result = &summary.best;
+        let lower_bound = summary.lower_bound;
         println!(
-            "Currently the best solution has cost of: {}",
-            best_solution.cost
+            "Gap to the assignment lower bound {}: {:.2}%",
+            lower_bound,
+            best_result.gap(lower_bound)
         );
-        best_solution
+
+        summary_dict(py, &summary)
     }
 }
 
-#[pymethods]
-impl PtsaAlgorithm {
-    #[new]
-    pub fn new(parameters: PyObject) -> Self {
-        Python::with_gil(|py| {
-            let params: Params = parameters.extract(py).unwrap();
-            PtsaAlgorithm { params }
-        })
+/// What `run_for` puts on the distance matrix
+#[cfg(feature = "python")]
+struct Problem {
+    kind: TourKind,
+    time_windows: Option<TimeWindows>,
+    fleet: Option<Fleet>,
+    clusters: Option<Clusters>,
+}
+
+#[cfg(feature = "python")]
+impl Problem {
+    fn attach<C: Cost>(&self, dmatrix: DistanceMatrix<C>) -> DistanceMatrix<C> {
+        let mut dmatrix = dmatrix.with_kind(self.kind);
+        if let Some(time_windows) = &self.time_windows {
+            dmatrix = dmatrix.with_time_windows(time_windows.clone());
+        }
+        if let Some(fleet) = &self.fleet {
+            dmatrix = dmatrix.with_fleet(fleet.clone());
+        }
+        match &self.clusters {
+            Some(clusters) => dmatrix.with_clusters(clusters.clone()),
+            None => dmatrix,
+        }
     }
+}
 
-    pub fn run_for(&self, matrix: Vec<Vec<f64>>, time: i64) -> PyResult<(Vec<usize>, f64)> {
-        // Run the PTSA algorithm on a given distance matrix
-        // for specified about of time (in seconds)
-        let dmatrix = DistanceMatrix::new(matrix);
+/// A problem of `solve_many`: a file path, or a matrix named by its position
+#[cfg(feature = "python")]
+fn problem_instance(index: usize, problem: &PyAny) -> PyResult<Instance> {
+    if let Ok(path) = problem.extract::<PathBuf>() {
+        return Ok(Instance::read(path)?);
+    }
+    let dmatrix = DistanceMatrix::try_new(problem.extract::<Vec<Vec<f64>>>()?)?;
+    Ok(Instance {
+        name: index.to_string(),
+        size: dmatrix.size,
+        weights: dmatrix.data,
+        kind: TourKind::Cycle,
+        precedences: vec![],
+        clusters: vec![],
+    })
+}
 
-        println!("Rust solver. Start!");
-        println!("See you in {} seconds!", time);
-        let best_result = self.run(dmatrix, time);
-        Ok((best_result.solution.path, best_result.cost))
+/// The `Result` dict of a run
+#[cfg(feature = "python")]
+fn summary_dict<C: Cost + ToPyObject>(py: Python, summary: &RunSummary<C>) -> PyResult<PyObject> {
+    let best_result = &summary.best;
+    let lower_bound = summary.lower_bound;
+    let result = PyDict::new(py);
+    result.set_item("solution", &best_result.solution.path)?;
+    result.set_item("cost", best_result.cost)?;
+    result.set_item("lower_bound", lower_bound)?;
+    result.set_item("gap", best_result.gap(lower_bound))?;
+    result.set_item("local_search_improvement", summary.local_search_improvement)?;
+    result.set_item("time_to_best", summary.time_to_best.as_secs_f64())?;
+    result.set_item("runtime", summary.elapsed.as_secs_f64())?;
+    result.set_item("parameters", params_dict(py, &summary.params)?)?;
+    let tabu = PyDict::new(py);
+    tabu.set_item("checked", summary.tabu.checked)?;
+    tabu.set_item("blocked", summary.tabu.blocked)?;
+    tabu.set_item("aspirated", summary.tabu.aspirated)?;
+    result.set_item("tabu", tabu)?;
+    result.set_item("feasible", summary.feasible)?;
+    result.set_item("lateness", &summary.lateness)?;
+    result.set_item("routes", &summary.routes)?;
+    result.set_item("loads", &summary.loads)?;
+    Ok(result.into())
+}
+
+/// One `BatchResult` dict per problem of `solve_many`
+#[cfg(feature = "python")]
+fn batch_list<C: Cost + ToPyObject>(
+    py: Python,
+    instances: &[Instance],
+    results: &[BatchResult<C>],
+) -> PyResult<PyObject> {
+    let list = PyList::empty(py);
+    for (instance, result) in instances.iter().zip(results) {
+        let item = PyDict::new(py);
+        item.set_item("name", &instance.name)?;
+        item.set_item("best", summary_dict(py, result.best())?)?;
+        let runs = result
+            .runs
+            .iter()
+            .map(|run| summary_dict(py, run))
+            .collect::<PyResult<Vec<PyObject>>>()?;
+        item.set_item("runs", runs)?;
+        list.append(item)?;
     }
+    Ok(list.into())
+}
+
+/// Parameters as a dict of `Parameters` fields
+#[cfg(feature = "python")]
+fn params_dict(py: Python, params: &Params) -> PyResult<PyObject> {
+    let json = serde_json::to_string(params).expect("Params always serialize");
+    let fields = py.import("json")?.call_method1("loads", (json,))?;
+    Ok(fields.into())
+}
+
+/// Parameters of a named preset as a dict of `Parameters` fields
+#[cfg(feature = "python")]
+#[pyfunction]
+fn preset(py: Python, name: &str) -> PyResult<PyObject> {
+    params_dict(py, &Params::preset(name)?)
 }
 
 /// A Python module implemented in Rust.
+#[cfg(feature = "python")]
 #[pymodule]
-fn ptsa_rust(_py: Python, m: &PyModule) -> PyResult<()> {
+fn ptsa_rust(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PtsaAlgorithm>()?;
+    m.add_function(wrap_pyfunction!(preset, m)?)?;
+    m.add("PtsaError", py.get_type::<PtsaError>())?;
+    m.add("InvalidMatrixError", py.get_type::<InvalidMatrixError>())?;
+    m.add(
+        "InvalidParametersError",
+        py.get_type::<InvalidParametersError>(),
+    )?;
     Ok(())
 }
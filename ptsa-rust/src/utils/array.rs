@@ -0,0 +1,21 @@
+use super::{cost::Cost, matrix::DistanceMatrix, validation::ValidationError};
+use numpy::{Element, PyReadonlyArray2};
+
+/// Builds the matrix straight from the NumPy buffer with a single copy,
+/// no Python object is touched per entry. Returns `None` when the array
+/// is not C-contiguous, so the caller can fall back to the generic path.
+pub fn matrix_from_array<C: Cost + Element>(
+    array: &PyReadonlyArray2<C>,
+) -> Result<Option<DistanceMatrix<C>>, ValidationError> {
+    let shape = array.shape();
+    if shape[0] != shape[1] {
+        return Err(ValidationError::Matrix(format!(
+            "expected a square matrix, got {}x{}",
+            shape[0], shape[1]
+        )));
+    }
+    Ok(array
+        .as_slice()
+        .ok()
+        .map(|data| DistanceMatrix::from_flat(data.to_vec(), shape[0])))
+}
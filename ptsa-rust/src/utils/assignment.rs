@@ -0,0 +1,188 @@
+use super::{cost::Cost, matrix::DistanceMatrix};
+
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    /// `successors[i]` is the city assigned to follow city `i`
+    pub successors: Vec<usize>,
+    pub cost: f64,
+    /// Dual potentials of rows and columns, `cost[i][j] - rows[i] - columns[j]`
+    /// is non-negative for every arc and zero on the assigned ones
+    pub row_potentials: Vec<f64>,
+    pub column_potentials: Vec<f64>,
+}
+
+impl Assignment {
+    /// How much more expensive the arc is than the assignment prices suggest
+    #[inline]
+    pub fn reduced_cost(&self, cost: f64, from: usize, to: usize) -> f64 {
+        cost - self.row_potentials[from] - self.column_potentials[to]
+    }
+
+    /// Splits the assignment into its disjoint directed cycles
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let size = self.successors.len();
+        let mut visited = vec![false; size];
+        let mut cycles = vec![];
+        for start in 0..size {
+            if visited[start] {
+                continue;
+            }
+            let mut cycle = vec![];
+            let mut city = start;
+            while !visited[city] {
+                visited[city] = true;
+                cycle.push(city);
+                city = self.successors[city];
+            }
+            cycles.push(cycle);
+        }
+        cycles
+    }
+}
+
+/// Solves the assignment problem on the distance matrix with the diagonal
+/// excluded, using the shortest augmenting path method (Jonker-Volgenant style)
+/// with row and column potentials. Runs in O(n^3).
+pub fn solve_assignment<C: Cost>(dmatrix: &DistanceMatrix<C>) -> Assignment {
+    solve_assignment_costs(&dmatrix.to_f64(), dmatrix.size)
+        .expect("Every matrix has a cyclic assignment")
+}
+
+/// Same as `solve_assignment` but for any square row-major costs, where infinite
+/// entries mark forbidden arcs. Returns `None` when no assignment avoids them.
+pub fn solve_assignment_costs(costs: &[f64], n: usize) -> Option<Assignment> {
+    assert!(n >= 2, "Assignment needs at least two cities");
+
+    // Everything is 1-indexed here, column 0 is the virtual starting column
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; n + 1];
+    let mut row_of_column = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for row in 1..=n {
+        row_of_column[0] = row;
+        let mut column = 0;
+        let mut min_slack = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[column] = true;
+            let current_row = row_of_column[column];
+            let mut delta = f64::INFINITY;
+            let mut next_column = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                // Staying in the same city is not an allowed assignment
+                if j != current_row {
+                    let reduced = costs[(current_row - 1) * n + j - 1] - u[current_row] - v[j];
+                    if reduced < min_slack[j] {
+                        min_slack[j] = reduced;
+                        way[j] = column;
+                    }
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    next_column = j;
+                }
+            }
+            if delta == f64::INFINITY {
+                return None;
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[row_of_column[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+            column = next_column;
+            if row_of_column[column] == 0 {
+                break;
+            }
+        }
+        // Augment along the alternating path
+        loop {
+            let previous = way[column];
+            row_of_column[column] = row_of_column[previous];
+            column = previous;
+            if column == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut successors = vec![0; n];
+    for j in 1..=n {
+        successors[row_of_column[j] - 1] = j - 1;
+    }
+    let cost = successors
+        .iter()
+        .enumerate()
+        .map(|(i, &j)| costs[i * n + j])
+        .sum();
+    Some(Assignment {
+        successors,
+        cost,
+        row_potentials: u.split_off(1),
+        column_potentials: v.split_off(1),
+    })
+}
+
+impl<C: Cost> DistanceMatrix<C> {
+    /// Assignment problem relaxation of the ATSP. Every tour is an assignment,
+    /// so its optimal value bounds the optimal tour length from below.
+    pub fn assignment_lower_bound(&self) -> f64 {
+        solve_assignment(self).cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assignment_avoids_diagonal() {
+        let dmatrix = DistanceMatrix::new(vec![
+            vec![0.0, 5.0, 1.0],
+            vec![1.0, 0.0, 5.0],
+            vec![5.0, 1.0, 0.0],
+        ]);
+        let assignment = solve_assignment(&dmatrix);
+        assert_eq!(assignment.successors, vec![2, 0, 1]);
+        assert_eq!(assignment.cost, 3.0);
+        for (from, &to) in assignment.successors.iter().enumerate() {
+            assert_eq!(
+                assignment.reduced_cost(dmatrix.cost(from, to), from, to),
+                0.0
+            );
+        }
+    }
+
+    #[test]
+    fn test_assignment_cycles() {
+        // Two cheap 2-cycles: 0 <-> 1 and 2 <-> 3
+        let dmatrix = DistanceMatrix::new(vec![
+            vec![0.0, 1.0, 9.0, 9.0],
+            vec![1.0, 0.0, 9.0, 9.0],
+            vec![9.0, 9.0, 0.0, 1.0],
+            vec![9.0, 9.0, 1.0, 0.0],
+        ]);
+        let assignment = solve_assignment(&dmatrix);
+        assert_eq!(assignment.cycles().len(), 2);
+        assert_eq!(dmatrix.assignment_lower_bound(), 4.0);
+    }
+
+    #[test]
+    fn test_assignment_infeasible() {
+        // City 0 may not leave to anywhere
+        let inf = f64::INFINITY;
+        let costs = vec![
+            0.0, inf, inf, //
+            1.0, 0.0, 1.0, //
+            1.0, 1.0, 0.0,
+        ];
+        assert!(solve_assignment_costs(&costs, 3).is_none());
+    }
+}
@@ -0,0 +1,116 @@
+use rand::Rng;
+
+use super::{
+    cost::Cost, matrix::DistanceMatrix, moves::Move, params::Params, random::rng,
+    solution::Solution,
+};
+
+/// Random moves whose cost change is sampled to place the temperature ladder
+const SAMPLED_MOVES: usize = 2000;
+/// Acceptance probability of a median worsening move at the hottest temperature
+const HOT_ACCEPTANCE: f64 = 0.5;
+/// Acceptance probability of a small (10th percentile) worsening move at the coldest
+const COLD_ACCEPTANCE: f64 = 0.01;
+/// Longest mutated segment the automatic mode aims for, in cities
+const TARGET_SEGMENT: f64 = 30.0;
+
+impl Params {
+    /// Derives the number of states, the ladder bounds, the segment length and
+    /// the cooling rate from the instance, keeping every other field.
+    ///
+    /// The bounds come from the cost change of random moves applied to a
+    /// nearest neighbour tour: the hottest replica accepts half of the typical
+    /// worsening moves, the coldest one almost none of the small ones. The
+    /// cooling rate takes the ladder from top to bottom in about `n` steps.
+    pub fn auto_tuned<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> Params {
+        let n = dmatrix.size;
+        let size = n as f64;
+        let number_of_states = ((5.0 * size.sqrt()).round() as usize).clamp(20, 64);
+        let max_length_percent_of_cycle = (TARGET_SEGMENT / size).clamp(0.05, 0.3);
+
+        let mut deltas = sample_worsening_deltas(dmatrix, max_length_percent_of_cycle);
+        deltas.sort_by(f64::total_cmp);
+        let (median, small) = match deltas.len() {
+            0 => {
+                // Every sampled move was free, fall back on the arc costs
+                let mean =
+                    dmatrix.data.iter().map(|cost| cost.to_f64()).sum::<f64>() / (n * n) as f64;
+                (f64::max(mean, 1.0), f64::max(mean, 1.0) / 10.0)
+            }
+            len => (deltas[len / 2], deltas[len / 10]),
+        };
+        let max_temperature = median / -HOT_ACCEPTANCE.ln();
+        let min_temperature = f64::min(small / -COLD_ACCEPTANCE.ln(), max_temperature / 10.0);
+        let cooling_rate = (min_temperature / max_temperature)
+            .powf(1.0 / size)
+            .clamp(0.8, 0.999);
+
+        Params {
+            number_of_states,
+            min_temperature,
+            max_temperature,
+            max_length_percent_of_cycle,
+            cooling_rate,
+            ..self.clone()
+        }
+    }
+}
+
+fn sample_worsening_deltas<C: Cost>(
+    dmatrix: &DistanceMatrix<C>,
+    max_length_percent_of_cycle: f64,
+) -> Vec<f64> {
+    let n = dmatrix.size;
+    let rng = &mut rng();
+    let longest = usize::max((n as f64 * max_length_percent_of_cycle).ceil() as usize, 1);
+    let mut solution = Solution::nearest_neightbor_solution(dmatrix, rng.gen_range(0..n));
+    let mut backup = vec![];
+    let mut deltas = Vec::with_capacity(SAMPLED_MOVES);
+    for _ in 0..SAMPLED_MOVES {
+        let length = rng.gen_range(1..=longest);
+        let proposal = if rng.gen_bool(0.5) {
+            Move::Shuffle {
+                start: rng.gen_range(0..n),
+                length,
+            }
+        } else {
+            Move::Swap {
+                first: rng.gen_range(0..n),
+                second: rng.gen_range(0..n),
+                length: usize::min(length, n / 2),
+            }
+        };
+        let removed = proposal.arcs_cost(&solution, dmatrix);
+        proposal.apply(&mut solution, &mut backup);
+        let added = proposal.arcs_cost(&solution, dmatrix);
+        proposal.undo(&mut solution, &backup);
+        let delta = (added - removed).to_f64();
+        if delta > 0.0 {
+            deltas.push(delta);
+        }
+    }
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_params_are_valid() {
+        let mut dmatrix = DistanceMatrix::<i64>::modular(60, [31, 17, 7, 23]);
+        for cost in &mut dmatrix.data {
+            *cost = *cost * 10 - 5;
+        }
+        let base = Params {
+            auto: true,
+            ..Params::default()
+        };
+        let tuned = base.auto_tuned(&dmatrix);
+        tuned.validate().unwrap();
+        assert_eq!(tuned.number_of_states, 39);
+        assert_eq!(tuned.max_length_percent_of_cycle, 0.3);
+        assert!(tuned.min_temperature < tuned.max_temperature);
+        assert_eq!(tuned.heuristics, base.heuristics);
+    }
+}
@@ -0,0 +1,136 @@
+use std::thread;
+
+use super::{
+    cost::Cost,
+    helpers::parallel_map,
+    matrix::DistanceMatrix,
+    solver::{RunSummary, Solver},
+    validation::ValidationError,
+};
+
+/// Every run of one instance of a batch
+#[derive(Debug, Clone)]
+pub struct BatchResult<C: Cost = f64> {
+    pub runs: Vec<RunSummary<C>>,
+}
+
+impl<C: Cost> BatchResult<C> {
+    /// The run with the cheapest tour, feasible ones first
+    pub fn best(&self) -> &RunSummary<C> {
+        self.runs
+            .iter()
+            .min_by(|a, b| {
+                b.feasible
+                    .cmp(&a.feasible)
+                    .then(a.best.cost.total_cmp(&b.best.cost))
+            })
+            .expect("A batch makes at least one run of every instance")
+    }
+}
+
+/// Independent runs of several instances on a pool of `jobs` threads.
+///
+/// Every instance is prepared once, its runs share the candidate lists and
+/// the heuristic tours. With a seed, run `r` of instance `i` is seeded with
+/// `seed + i * runs + r`.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub solver: Solver,
+    /// Runs of every instance
+    pub runs: usize,
+    pub jobs: usize,
+}
+
+impl Batch {
+    /// Enough runs at the same time to keep the threads of every solver busy
+    pub fn default_jobs(solver: &Solver) -> usize {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        usize::max(threads / solver.params.number_of_repeats, 1)
+    }
+
+    pub fn solve<C: Cost>(
+        &self,
+        matrices: &[DistanceMatrix<C>],
+    ) -> Result<Vec<BatchResult<C>>, ValidationError> {
+        if self.runs == 0 || self.jobs == 0 {
+            return Err(ValidationError::Parameters(
+                "a batch needs at least one run and one job".to_string(),
+            ));
+        }
+        let prepared = parallel_map(self.jobs, matrices.len(), |i| {
+            self.solver.prepare(&matrices[i])
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let mut summaries = parallel_map(self.jobs, matrices.len() * self.runs, |task| {
+            let solver = Solver {
+                seed: self.solver.seed.map(|seed| seed.wrapping_add(task as u64)),
+                ..self.solver.clone()
+            };
+            solver.solve_prepared(&prepared[task / self.runs])
+        })
+        .into_iter();
+        Ok((0..matrices.len())
+            .map(|_| BatchResult {
+                runs: summaries.by_ref().take(self.runs).collect(),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Stop};
+
+    fn matrix(size: usize) -> DistanceMatrix<i64> {
+        DistanceMatrix::modular(size, [7, 3, 0, 11])
+    }
+
+    fn solver() -> Solver {
+        Solver::builder()
+            .params(Params {
+                number_of_repeats: 2,
+                number_of_states: 4,
+                ..Params::default()
+            })
+            .stop(Stop::after_iterations(10))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_runs_every_instance() {
+        let matrices = vec![matrix(24), matrix(30)];
+        let batch = Batch {
+            solver: solver(),
+            runs: 3,
+            jobs: 2,
+        };
+        let results = batch.solve(&matrices).unwrap();
+        assert_eq!(results.len(), 2);
+        for (result, dmatrix) in results.iter().zip(matrices.iter()) {
+            assert_eq!(result.runs.len(), 3);
+            let best = result.best();
+            assert_eq!(best.best.cost, best.best.solution.cost(dmatrix));
+            assert!(result
+                .runs
+                .iter()
+                .all(|run| run.best.cost >= best.best.cost));
+        }
+    }
+
+    #[test]
+    fn test_best_prefers_feasible_runs() {
+        let feasible = solver().solve(&matrix(24)).unwrap();
+        let mut infeasible = feasible.clone();
+        infeasible.best.cost -= 5;
+        infeasible.feasible = false;
+        let result = BatchResult {
+            runs: vec![infeasible, feasible.clone()],
+        };
+        assert!(result.best().feasible);
+        assert_eq!(result.best().best.cost, feasible.best.cost);
+    }
+}
@@ -0,0 +1,323 @@
+use std::{path::Path, time::Duration};
+
+use super::{cost::Cost, instance::KnownInstance, solver::Solver, validation::ValidationError};
+
+/// One run of an instance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkRun {
+    pub seed: u64,
+    pub cost: f64,
+    pub time_to_best: Duration,
+    pub elapsed: Duration,
+}
+
+/// Every run of one instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceRuns {
+    pub name: String,
+    pub size: usize,
+    pub optimum: f64,
+    pub runs: Vec<BenchmarkRun>,
+}
+
+impl InstanceRuns {
+    pub fn best(&self) -> f64 {
+        self.runs
+            .iter()
+            .map(|run| run.cost)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.runs.iter().map(|run| run.cost).sum::<f64>() / self.runs.len() as f64
+    }
+
+    /// Sample standard deviation of the costs, zero for a single run
+    pub fn std(&self) -> f64 {
+        if self.runs.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let squares: f64 = self.runs.iter().map(|run| (run.cost - mean).powi(2)).sum();
+        (squares / (self.runs.len() - 1) as f64).sqrt()
+    }
+
+    /// Deficit of the best run over the optimum, in percent of the optimum
+    pub fn deficit_ratio(&self) -> f64 {
+        (self.best() - self.optimum) / self.optimum * 100.0
+    }
+
+    /// Mean time the runs took to find their tour
+    pub fn time_to_best(&self) -> Duration {
+        self.runs
+            .iter()
+            .map(|run| run.time_to_best)
+            .sum::<Duration>()
+            / self.runs.len() as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Markdown,
+    Latex,
+}
+
+impl TableFormat {
+    /// Guessed from the extension: `.md` and `.tex`, CSV otherwise
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("md") => TableFormat::Markdown,
+            Some("tex") => TableFormat::Latex,
+            _ => TableFormat::Csv,
+        }
+    }
+}
+
+/// Results of a benchmark, instances ordered by size
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub instances: Vec<InstanceRuns>,
+}
+
+const COLUMNS: [&str; 7] = [
+    "Name",
+    "Best known solution",
+    "Our best solution",
+    "Mean",
+    "Std",
+    "Deficit ratio",
+    "Time to best [s]",
+];
+
+impl Report {
+    pub fn render(&self, format: TableFormat) -> String {
+        match format {
+            TableFormat::Csv => self.to_csv(),
+            TableFormat::Markdown => self.to_markdown(),
+            TableFormat::Latex => self.to_latex(),
+        }
+    }
+
+    /// One row per run, the statistics are left to the reader
+    pub fn to_csv(&self) -> String {
+        let mut text =
+            String::from("name,size,best_known,run,seed,cost,deficit_ratio,time_to_best,runtime\n");
+        for instance in self.instances.iter() {
+            for (i, run) in instance.runs.iter().enumerate() {
+                text.push_str(&format!(
+                    "{},{},{},{},{},{},{},{},{}\n",
+                    instance.name,
+                    instance.size,
+                    instance.optimum,
+                    i,
+                    run.seed,
+                    run.cost,
+                    (run.cost - instance.optimum) / instance.optimum * 100.0,
+                    run.time_to_best.as_secs_f64(),
+                    run.elapsed.as_secs_f64()
+                ));
+            }
+        }
+        text
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut text = format!("| {} |\n", COLUMNS.join(" | "));
+        text.push_str("|:--|--:|--:|--:|--:|--:|--:|\n");
+        for row in self.rows(|name| name.to_string()) {
+            text.push_str(&format!("| {} |\n", row.join(" | ")));
+        }
+        text
+    }
+
+    /// A table in the layout of `src/Final_results/our_results.tex`
+    pub fn to_latex(&self) -> String {
+        let mut text =
+            String::from("\\begin{table}\n\\centering\n\\begin{tabular}{l|r|r|r|r|r|r}\n\\hline\n");
+        text.push_str(&format!("{}\\\\\n\\hline\n", COLUMNS.join(" & ")));
+        for row in self.rows(|name| name.replace('_', "\\_")) {
+            text.push_str(&format!("{}\\\\\n\\hline\n", row.join(" & ")));
+        }
+        text.push_str("\\end{tabular}\n\\end{table}\n");
+        text
+    }
+
+    fn rows(&self, escape: impl Fn(&str) -> String) -> Vec<Vec<String>> {
+        self.instances
+            .iter()
+            .map(|instance| {
+                vec![
+                    escape(&instance.name),
+                    instance.optimum.to_string(),
+                    instance.best().to_string(),
+                    format!("{:.1}", instance.mean()),
+                    format!("{:.1}", instance.std()),
+                    format!("{:.7}", instance.deficit_ratio()),
+                    format!("{:.2}", instance.time_to_best().as_secs_f64()),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Runs every instance `runs` times with the same solver, run `r` seeded
+/// with `seed + r`. Runs stop early at the optimum.
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    pub solver: Solver,
+    pub runs: usize,
+    pub seed: u64,
+    /// Print every finished run
+    pub verbose: bool,
+}
+
+impl Benchmark {
+    pub fn run(&self, instances: &[KnownInstance]) -> Result<Report, ValidationError> {
+        if self.runs == 0 {
+            return Err(ValidationError::Parameters(
+                "a benchmark needs at least one run".to_string(),
+            ));
+        }
+        let mut order: Vec<&KnownInstance> = instances.iter().collect();
+        order.sort_by(|a, b| {
+            (a.instance.size, &a.instance.name).cmp(&(b.instance.size, &b.instance.name))
+        });
+        let mut results = vec![];
+        for known in order {
+            let mut runs = vec![];
+            for r in 0..self.runs {
+                let seed = self.seed.wrapping_add(r as u64);
+                let run = self.run_once(known, seed)?;
+                if self.verbose {
+                    println!(
+                        "{} run {} cost={} deficit={:.2}% time_to_best={:.2}s",
+                        known.instance.name,
+                        r,
+                        run.cost,
+                        known.deficit(run.cost),
+                        run.time_to_best.as_secs_f64()
+                    );
+                }
+                runs.push(run);
+            }
+            results.push(InstanceRuns {
+                name: known.instance.name.clone(),
+                size: known.instance.size,
+                optimum: known.optimum,
+                runs,
+            });
+        }
+        Ok(Report { instances: results })
+    }
+
+    fn run_once(&self, known: &KnownInstance, seed: u64) -> Result<BenchmarkRun, ValidationError> {
+        let solver = Solver {
+            stop: self.solver.stop.or_at_cost(known.optimum),
+            seed: Some(seed),
+            ..self.solver.clone()
+        };
+        let (cost, time_to_best, elapsed) = if known.instance.is_integral() {
+            let summary = solver.solve(&known.instance.matrix::<i64>())?;
+            (
+                summary.best.cost.to_f64(),
+                summary.time_to_best,
+                summary.elapsed,
+            )
+        } else {
+            let summary = solver.solve(&known.instance.matrix::<f64>())?;
+            (summary.best.cost, summary.time_to_best, summary.elapsed)
+        };
+        Ok(BenchmarkRun {
+            seed,
+            cost,
+            time_to_best,
+            elapsed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        utils::{instance::Instance, matrix::DistanceMatrix, params::Params, tour::TourKind},
+        Stop,
+    };
+
+    fn report() -> Report {
+        let run = |cost: f64, seconds: u64| BenchmarkRun {
+            seed: 0,
+            cost,
+            time_to_best: Duration::from_secs(seconds),
+            elapsed: Duration::from_secs(10),
+        };
+        Report {
+            instances: vec![InstanceRuns {
+                name: "ftv_33".to_string(),
+                size: 34,
+                optimum: 100.0,
+                runs: vec![run(110.0, 2), run(120.0, 4)],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_statistics() {
+        let instance = &report().instances[0];
+        assert_eq!(instance.best(), 110.0);
+        assert_eq!(instance.mean(), 115.0);
+        assert!((instance.std() - 50.0_f64.sqrt()).abs() < 1e-9);
+        assert!((instance.deficit_ratio() - 10.0).abs() < 1e-9);
+        assert_eq!(instance.time_to_best(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_tables() {
+        let report = report();
+        assert_eq!(report.to_csv().lines().count(), 3);
+        assert!(report
+            .to_markdown()
+            .contains("| ftv_33 | 100 | 110 | 115.0 | 7.1 | 10.0000000 | 3.00 |"));
+        assert!(report
+            .to_latex()
+            .contains("ftv\\_33 & 100 & 110 & 115.0 & 7.1 & 10.0000000 & 3.00\\\\"));
+    }
+
+    #[test]
+    fn test_runs_every_instance() {
+        let size = 10;
+        let weights = DistanceMatrix::<f64>::modular(size, [5, 11, 0, 17]).data;
+        let known = KnownInstance {
+            instance: Instance {
+                name: "tiny".to_string(),
+                size,
+                weights,
+                kind: TourKind::Cycle,
+                precedences: vec![],
+                clusters: vec![],
+            },
+            optimum: 10.0,
+        };
+        let benchmark = Benchmark {
+            solver: Solver::builder()
+                .params(Params {
+                    number_of_repeats: 2,
+                    number_of_states: 4,
+                    ..Params::default()
+                })
+                .stop(Stop::after_iterations(10))
+                .build()
+                .unwrap(),
+            runs: 3,
+            seed: 5,
+            verbose: false,
+        };
+        let report = benchmark.run(&[known]).unwrap();
+        let runs = &report.instances[0].runs;
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[2].seed, 7);
+        assert!(runs.iter().all(|run| run.cost >= 10.0));
+    }
+}
@@ -0,0 +1,330 @@
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    cost::Cost,
+    matrix::DistanceMatrix,
+    moves::Move,
+    random::rng,
+    solution::Solution,
+    validation::{ValidationError, MIN_DIMENSION},
+};
+
+/// Clusters of a generalized TSP, whose tours visit exactly one city of each.
+/// Such a tour has a position per cluster: the path lists the clusters in
+/// the order of the tour, each by the city chosen for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clusters {
+    pub members: Vec<Vec<usize>>,
+    /// Cluster of every city, `usize::MAX` for a city of none
+    cluster_of: Vec<usize>,
+}
+
+impl Clusters {
+    pub fn new(members: Vec<Vec<usize>>) -> Self {
+        let size = members.iter().flatten().max().map_or(0, |&city| city + 1);
+        let mut cluster_of = vec![usize::MAX; size];
+        for (cluster, cities) in members.iter().enumerate() {
+            for &city in cities {
+                cluster_of[city] = cluster;
+            }
+        }
+        Clusters {
+            members,
+            cluster_of,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.members.len()
+    }
+
+    #[inline]
+    pub fn cluster_of(&self, city: usize) -> usize {
+        self.cluster_of[city]
+    }
+
+    /// Every city of the matrix has to be in exactly one cluster
+    pub fn validate(&self, size: usize) -> Result<(), ValidationError> {
+        let invalid = |message: String| Err(ValidationError::Matrix(message));
+        if self.count() < MIN_DIMENSION {
+            return invalid(format!(
+                "at least {} clusters are needed, got {}",
+                MIN_DIMENSION,
+                self.count()
+            ));
+        }
+        let mut listed = vec![false; size];
+        for (cluster, cities) in self.members.iter().enumerate() {
+            if cities.is_empty() {
+                return invalid(format!("cluster {} is empty", cluster));
+            }
+            for &city in cities {
+                if city >= size {
+                    return invalid(format!(
+                        "cluster {} has the city {}, the matrix has {} cities",
+                        cluster, city, size
+                    ));
+                }
+                if listed[city] {
+                    return invalid(format!("city {} is listed more than once", city));
+                }
+                listed[city] = true;
+            }
+        }
+        if let Some(city) = listed.iter().position(|&listed| !listed) {
+            return invalid(format!("city {} is in no cluster", city));
+        }
+        Ok(())
+    }
+
+    /// Cheapest arc from every cluster to every other. Its tours are the
+    /// cluster orders and cost no more than any tour visiting the clusters
+    /// in that order, so its bounds hold for the instance.
+    pub fn matrix<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> DistanceMatrix<C> {
+        let m = self.count();
+        let mut data = Vec::with_capacity(m * m);
+        for from in &self.members {
+            for to in &self.members {
+                let cheapest = if std::ptr::eq(from, to) {
+                    C::ZERO
+                } else {
+                    from.iter()
+                        .flat_map(|&i| to.iter().map(move |&j| dmatrix.cost(i, j)))
+                        .min_by(|a, b| a.total_cmp(b))
+                        .unwrap()
+                };
+                data.push(cheapest);
+            }
+        }
+        DistanceMatrix::from_flat(data, m)
+    }
+
+    /// The clusters in the order a tour visits them
+    pub fn order(&self, solution: &Solution) -> Solution {
+        Solution::new(
+            solution
+                .path
+                .iter()
+                .map(|&city| self.cluster_of[city])
+                .collect(),
+        )
+    }
+
+    /// Cheapest tour visiting the clusters in `order`: the shortest cycle
+    /// through the layers of their cities, started from every city of the
+    /// smallest cluster
+    pub fn choose<C: Cost>(&self, dmatrix: &DistanceMatrix<C>, order: &Solution) -> Solution {
+        let m = order.size;
+        let first = (0..m)
+            .min_by_key(|&k| self.members[order.path[k]].len())
+            .unwrap();
+        let layers: Vec<&[usize]> = (0..m)
+            .map(|k| self.members[order.path[(first + k) % m]].as_slice())
+            .collect();
+        // Index of the city of the layer before on the shortest way to each city
+        let mut previous: Vec<Vec<usize>> =
+            layers.iter().map(|layer| vec![0; layer.len()]).collect();
+        let mut best: Option<(C, Vec<usize>)> = None;
+        for &start in layers[0] {
+            let mut cities = std::slice::from_ref(&start);
+            let mut costs = vec![C::ZERO];
+            for k in 1..m {
+                let layer = layers[k];
+                let mut next = Vec::with_capacity(layer.len());
+                for (j, &to) in layer.iter().enumerate() {
+                    let (i, cost) = cheapest(
+                        cities
+                            .iter()
+                            .zip(&costs)
+                            .map(|(&from, &cost)| cost + dmatrix.cost(from, to)),
+                    );
+                    previous[k][j] = i;
+                    next.push(cost);
+                }
+                cities = layer;
+                costs = next;
+            }
+            let (mut j, cost) = cheapest(
+                cities
+                    .iter()
+                    .zip(&costs)
+                    .map(|(&from, &cost)| cost + dmatrix.cost(from, start)),
+            );
+            if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+                let mut path = vec![start; m];
+                for k in (1..m).rev() {
+                    path[k] = layers[k][j];
+                    j = previous[k][j];
+                }
+                best = Some((cost, path));
+            }
+        }
+        Solution::new(best.unwrap().1)
+    }
+
+    /// Chooses the cities of a tour anew for its cluster order, returns by
+    /// how much its cost went down
+    pub fn reselect<C: Cost>(&self, dmatrix: &DistanceMatrix<C>, solution: &mut Solution) -> C {
+        let before = solution.cost(dmatrix);
+        let chosen = self.choose(dmatrix, &self.order(solution));
+        let after = chosen.cost(dmatrix);
+        if after < before {
+            *solution = chosen;
+            before - after
+        } else {
+            C::ZERO
+        }
+    }
+
+    /// The clusters in a random order, each at a random city of it
+    pub fn random_solution(&self) -> Solution {
+        let order = Solution::random_solution(self.count());
+        let rng = &mut rng();
+        Solution::new(
+            order
+                .path
+                .iter()
+                .map(|&cluster| *self.members[cluster].choose(rng).unwrap())
+                .collect(),
+        )
+    }
+
+    /// Another city for the cluster at a random position, `None` when that
+    /// cluster has a single city
+    pub fn propose(&self, solution: &Solution) -> Option<Move> {
+        let rng = &mut rng();
+        let position = rng.gen_range(0..solution.size);
+        let current = solution.path[position];
+        let members = &self.members[self.cluster_of[current]];
+        if members.len() < 2 {
+            return None;
+        }
+        let city = loop {
+            let city = *members.choose(rng).unwrap();
+            if city != current {
+                break city;
+            }
+        };
+        Some(Move::Choose { position, city })
+    }
+}
+
+/// Index and value of the smallest cost
+fn cheapest<C: Cost>(costs: impl Iterator<Item = C>) -> (usize, C) {
+    costs
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn matrix(size: usize) -> DistanceMatrix<i64> {
+        DistanceMatrix::modular(size, [7, 13, 5, 29])
+    }
+
+    /// Four clusters of 12 cities, of sizes 2 to 4
+    fn clusters() -> Clusters {
+        Clusters::new(vec![
+            vec![0, 5, 9],
+            vec![1, 6],
+            vec![2, 7, 10, 11],
+            vec![3, 4, 8],
+        ])
+    }
+
+    #[test]
+    fn test_choose_is_the_cheapest_for_the_order() {
+        let clusters = clusters();
+        let dmatrix = matrix(12).with_clusters(clusters.clone());
+        let order = Solution::new(vec![2, 0, 3, 1]);
+        let chosen = clusters.choose(&dmatrix, &order);
+        assert_eq!(clusters.order(&chosen).path.len(), 4);
+        // Every choice of a city per cluster in that order
+        let mut cheapest = i64::MAX;
+        for &a in &clusters.members[2] {
+            for &b in &clusters.members[0] {
+                for &c in &clusters.members[3] {
+                    for &d in &clusters.members[1] {
+                        cheapest = cheapest.min(Solution::new(vec![a, b, c, d]).cost(&dmatrix));
+                    }
+                }
+            }
+        }
+        assert_eq!(chosen.cost(&dmatrix), cheapest);
+        let mut order_of_chosen = clusters.order(&chosen).path;
+        let at = order_of_chosen
+            .iter()
+            .position(|&cluster| cluster == 2)
+            .unwrap();
+        order_of_chosen.rotate_left(at);
+        assert_eq!(order_of_chosen, order.path);
+    }
+
+    #[test]
+    fn test_cluster_matrix_is_a_bound() {
+        let clusters = clusters();
+        let dmatrix = matrix(12).with_clusters(clusters.clone());
+        let between = clusters.matrix(&dmatrix);
+        assert_eq!(between.size, 4);
+        assert_eq!(between.cost(1, 1), 0);
+        assert_eq!(
+            between.cost(1, 3),
+            [1, 6]
+                .iter()
+                .flat_map(|&i| [3, 4, 8].map(|j| dmatrix.cost(i, j)))
+                .min()
+                .unwrap()
+        );
+        let tour = Solution::new(vec![9, 6, 11, 4]);
+        assert!(clusters.order(&tour).cost(&between) <= tour.cost(&dmatrix));
+    }
+
+    #[test]
+    fn test_rejects_bad_clusters() {
+        assert!(clusters().validate(12).is_ok());
+        assert!(clusters().validate(13).is_err());
+        assert!(Clusters::new(vec![vec![0, 1], vec![2], vec![1, 3]])
+            .validate(4)
+            .is_err());
+        assert!(Clusters::new(vec![vec![0, 1], vec![2, 3]])
+            .validate(4)
+            .is_err());
+        assert!(Clusters::new(vec![vec![0], vec![1], vec![2, 4], vec![]])
+            .validate(5)
+            .is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_choice_delta_matches_cost_and_undo_restores(
+            order in Just((0..4).collect::<Vec<usize>>()).prop_shuffle(),
+            picks in prop::collection::vec(0usize..4, 4),
+            position in 0usize..4,
+            pick in 0usize..4,
+        ) {
+            let clusters = clusters();
+            let dmatrix = matrix(12).with_clusters(clusters.clone());
+            let city = |cluster: usize, pick: usize| {
+                let members = &clusters.members[cluster];
+                members[pick % members.len()]
+            };
+            let path: Vec<usize> = order.iter().zip(&picks).map(|(&c, &p)| city(c, p)).collect();
+            let mut solution = Solution::new(path.clone());
+            let proposal = Move::Choose { position, city: city(order[position], pick) };
+            let before = solution.cost(&dmatrix);
+            let mut backup = vec![];
+            let removed = proposal.arcs_cost(&solution, &dmatrix);
+            proposal.apply(&mut solution, &mut backup);
+            let added = proposal.arcs_cost(&solution, &dmatrix);
+            prop_assert_eq!(before - removed + added, solution.cost(&dmatrix));
+            prop_assert_eq!(clusters.order(&solution).path, order);
+            proposal.undo(&mut solution, &backup);
+            prop_assert_eq!(solution.path, path);
+        }
+    }
+}
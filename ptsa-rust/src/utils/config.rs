@@ -0,0 +1,321 @@
+use std::{fmt, fs, path::Path, str::FromStr, time::Duration};
+
+use serde_json::{Map, Value};
+
+use super::{params::Params, solver::Stop, validation::ValidationError};
+
+/// Named parameter sets, selectable from Rust, Python and the CLI. The
+/// experiments ran every instance size with the same parameters, so `Small`
+/// and `Large` differ only in their time budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// The defaults of the Python `Parameters` dataclass
+    Default,
+    /// Settings of the experiments for instances below 300 cities,
+    /// run in 30 second restarts
+    Small,
+    /// The parameters of `Small` for 300 cities and more, run in 150 second
+    /// restarts
+    Large,
+}
+
+/// Instance size from which `Preset::Large` is used
+pub const LARGE_INSTANCE_SIZE: usize = 300;
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Default, Preset::Small, Preset::Large];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Default => "default",
+            Preset::Small => "small",
+            Preset::Large => "large",
+        }
+    }
+
+    /// The experiment preset matching the number of cities
+    pub fn for_size(size: usize) -> Self {
+        if size < LARGE_INSTANCE_SIZE {
+            Preset::Small
+        } else {
+            Preset::Large
+        }
+    }
+
+    pub fn params(&self) -> Params {
+        match self {
+            Preset::Default => Params::default(),
+            // `set_parameters` of src/Code/run_algorithm.py, only its time
+            // limit depends on the instance size
+            Preset::Small | Preset::Large => Params {
+                number_of_states: 20,
+                min_temperature: 0.1,
+                max_temperature: 50.0,
+                probability_of_shuffle: 0.1,
+                probability_of_heuristic: 0.7,
+                temp_beta_a: 1.0,
+                temp_beta_b: 1.0,
+                max_length_percent_of_cycle: 0.3,
+                swap_states_probability: 0.1,
+                closeness: 1.5,
+                cooling_rate: 0.95,
+                ..Params::default()
+            },
+        }
+    }
+
+    /// Time budget of one run the preset was used with
+    pub fn time(&self) -> Duration {
+        match self {
+            Preset::Default => Duration::from_secs(60),
+            Preset::Small => Duration::from_secs(30),
+            Preset::Large => Duration::from_secs(150),
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = ValidationError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == name)
+            .ok_or_else(|| {
+                ValidationError::Parameters(format!(
+                    "unknown preset {:?}, expected one of: default, small, large",
+                    name
+                ))
+            })
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(String),
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "Could not read the config: {}", error),
+            ConfigError::Parse(message) => write!(f, "Malformed config: {}", message),
+            ConfigError::Invalid(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<ValidationError> for ConfigError {
+    fn from(error: ValidationError) -> Self {
+        ConfigError::Invalid(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Guessed from the extension, TOML when unknown
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+/// Parameters and stopping criteria of a config file.
+///
+/// The file is a flat table of `Params` fields, starting from the `preset`
+/// key when given (`default` otherwise), plus the optional `time` (seconds),
+/// `iterations` and `target_cost` limits. Unknown keys are errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub params: Params,
+    pub stop: Stop,
+}
+
+impl Config {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        Config::parse(&fs::read_to_string(path)?, ConfigFormat::of(path))
+    }
+
+    pub fn parse(text: &str, format: ConfigFormat) -> Result<Self, ConfigError> {
+        let parse_error = |error: &dyn fmt::Display| ConfigError::Parse(error.to_string());
+        let mut table = parse_table(text, format)?;
+
+        let preset = match table.remove("preset") {
+            Some(Value::String(name)) => Some(name.parse::<Preset>()?),
+            Some(other) => {
+                return Err(ConfigError::Parse(format!(
+                    "preset {} is not a name",
+                    other
+                )))
+            }
+            None => None,
+        };
+        let mut take = |key: &str| -> Result<Option<f64>, ConfigError> {
+            match table.remove(key) {
+                Some(value) => value.as_f64().map(Some).ok_or_else(|| {
+                    ConfigError::Parse(format!("{} has to be a number, got {}", key, value))
+                }),
+                None => Ok(None),
+            }
+        };
+        let time = take("time")?;
+        let iterations = take("iterations")?;
+        let target_cost = take("target_cost")?;
+        if let Some(time) = time.filter(|time| !time.is_finite() || *time < 0.0) {
+            return Err(
+                ValidationError::Parameters(format!("time {} is not a duration", time)).into(),
+            );
+        }
+        if let Some(iterations) = iterations.filter(|iterations| {
+            !iterations.is_finite() || *iterations < 0.0 || iterations.fract() != 0.0
+        }) {
+            return Err(ValidationError::Parameters(format!(
+                "iterations {} is not a whole number of steps",
+                iterations
+            ))
+            .into());
+        }
+
+        let mut params = serde_json::to_value(preset.unwrap_or(Preset::Default).params())
+            .expect("Params always serialize");
+        let fields = params.as_object_mut().unwrap();
+        for (key, value) in table {
+            if !fields.contains_key(&key) {
+                return Err(ConfigError::Parse(format!("unknown parameter {:?}", key)));
+            }
+            fields.insert(key, value);
+        }
+        let params: Params = serde_json::from_value(params).map_err(|e| parse_error(&e))?;
+        params.validate()?;
+
+        let stop = Stop {
+            time: time
+                .map(Duration::from_secs_f64)
+                .or_else(|| preset.map(|preset| preset.time())),
+            iterations: iterations.map(|iterations| iterations as usize),
+            target_cost,
+        };
+        Ok(Config { params, stop })
+    }
+}
+
+/// Top-level table of a TOML, JSON or YAML document, empty for an empty file
+pub(crate) fn parse_table(
+    text: &str,
+    format: ConfigFormat,
+) -> Result<Map<String, Value>, ConfigError> {
+    let parse_error = |error: &dyn fmt::Display| ConfigError::Parse(error.to_string());
+    let value: Value = match format {
+        ConfigFormat::Toml => toml::from_str(text).map_err(|e| parse_error(&e))?,
+        ConfigFormat::Json => serde_json::from_str(text).map_err(|e| parse_error(&e))?,
+        ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|e| parse_error(&e))?,
+    };
+    match value {
+        Value::Object(table) => Ok(table),
+        Value::Null => Ok(Map::new()),
+        _ => Err(ConfigError::Parse(
+            "expected a table of parameters".to_string(),
+        )),
+    }
+}
+
+impl Params {
+    /// Parameters of a TOML, JSON or YAML config file, validated
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Config::read(path).map(|config| config.params)
+    }
+
+    pub fn preset(name: &str) -> Result<Self, ValidationError> {
+        name.parse::<Preset>().map(|preset| preset.params())
+    }
+
+    /// Writes every field in the format of the extension, readable by `from_file`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let serialize_error = |error: &dyn fmt::Display| ConfigError::Parse(error.to_string());
+        let text = match ConfigFormat::of(path) {
+            ConfigFormat::Toml => toml::to_string(self).map_err(|e| serialize_error(&e))?,
+            ConfigFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|e| serialize_error(&e))?
+            }
+            ConfigFormat::Yaml => serde_yaml::to_string(self).map_err(|e| serialize_error(&e))?,
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_agree() {
+        let toml =
+            "preset = \"small\"\ncooling_rate = 0.9\nheuristics = [\"greedy_edge\"]\ntime = 5\n";
+        let json =
+            r#"{"preset": "small", "cooling_rate": 0.9, "heuristics": ["greedy_edge"], "time": 5}"#;
+        let yaml = "preset: small\ncooling_rate: 0.9\nheuristics: [greedy_edge]\ntime: 5\n";
+        let config = Config::parse(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(config, Config::parse(json, ConfigFormat::Json).unwrap());
+        assert_eq!(config, Config::parse(yaml, ConfigFormat::Yaml).unwrap());
+        assert_eq!(config.params.number_of_states, 20);
+        assert_eq!(config.params.cooling_rate, 0.9);
+        assert_eq!(config.stop.time, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_rejects_bad_configs() {
+        let parse = |text: &str| Config::parse(text, ConfigFormat::Toml);
+        assert!(matches!(
+            parse("cooling_rat = 0.9"),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            parse("cooling_rate = 2.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("preset = \"huge\""),
+            Err(ConfigError::Invalid(_))
+        ));
+        for iterations in ["-5", "2.7"] {
+            assert!(matches!(
+                parse(&format!("iterations = {}", iterations)),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+        assert!(parse("iterations = inf").is_err());
+        let config = parse("iterations = 200").unwrap();
+        assert_eq!(config.stop.iterations, Some(200));
+    }
+
+    #[test]
+    fn test_preset_for_size() {
+        assert_eq!(Preset::for_size(171), Preset::Small);
+        assert_eq!(Preset::for_size(358), Preset::Large);
+        assert_eq!(Preset::Large.params(), Params::preset("large").unwrap());
+        assert_eq!(Preset::Large.params(), Preset::Small.params());
+        assert!(Preset::Large.time() > Preset::Small.time());
+    }
+}
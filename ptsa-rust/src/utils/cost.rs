@@ -0,0 +1,108 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    iter::Sum,
+    ops::{Add, Sub},
+};
+
+/// Numeric type of the distances. Integer instances keep exact arithmetic,
+/// so tour costs match the published optima bit-for-bit; the annealing itself
+/// (temperatures, acceptance) always works on `f64` views of the costs.
+pub trait Cost:
+    Copy
+    + Debug
+    + Display
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Sum
+    + Send
+    + Sync
+    + 'static
+{
+    const ZERO: Self;
+    /// Stands in for an unknown or forbidden cost
+    const INFINITY: Self;
+    /// Smallest difference that counts as an improvement
+    const EPSILON: Self;
+
+    fn to_f64(self) -> f64;
+    fn from_f64(value: f64) -> Self;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+    fn is_finite(self) -> bool;
+    /// The sum, or `None` when it does not fit the type
+    fn checked_add(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! integer_cost {
+    ($($t:ty),*) => {$(
+        impl Cost for $t {
+            const ZERO: Self = 0;
+            const INFINITY: Self = <$t>::MAX;
+            const EPSILON: Self = 0;
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn from_f64(value: f64) -> Self {
+                value.round() as $t
+            }
+
+            #[inline]
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                self.cmp(other)
+            }
+
+            #[inline]
+            fn is_finite(self) -> bool {
+                true
+            }
+
+            #[inline]
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$t>::checked_add(self, other)
+            }
+        }
+    )*};
+}
+
+macro_rules! float_cost {
+    ($($t:ty => $epsilon:expr),*) => {$(
+        impl Cost for $t {
+            const ZERO: Self = 0.0;
+            const INFINITY: Self = <$t>::INFINITY;
+            const EPSILON: Self = $epsilon;
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn from_f64(value: f64) -> Self {
+                value as $t
+            }
+
+            #[inline]
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                <$t>::total_cmp(self, other)
+            }
+
+            #[inline]
+            fn is_finite(self) -> bool {
+                <$t>::is_finite(self)
+            }
+
+            #[inline]
+            fn checked_add(self, other: Self) -> Option<Self> {
+                Some(self + other).filter(|sum| sum.is_finite())
+            }
+        }
+    )*};
+}
+
+integer_cost!(i32, i64);
+float_cost!(f32 => 1e-5, f64 => 1e-9);
@@ -0,0 +1,342 @@
+use std::str::FromStr;
+
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{cost::Cost, matrix::DistanceMatrix, random::rng, solution::Solution};
+
+/// AB-cycles tried by the edge assembly, the cheapest offspring is kept
+const ASSEMBLY_TRIALS: usize = 8;
+
+/// Recombinations of two parent tours that keep the direction of their arcs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossover {
+    /// OX: a segment of the first parent, then the other cities in the
+    /// order of the second
+    Order,
+    /// Follows arcs of either parent, to the city with the fewest arcs left
+    EdgeRecombination,
+    /// EAX: an AB-cycle of alternating parent arcs replaces arcs of the first
+    /// parent, the subtours it leaves are merged greedily
+    EdgeAssembly,
+}
+
+impl FromStr for Crossover {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "order" => Ok(Crossover::Order),
+            "edge_recombination" => Ok(Crossover::EdgeRecombination),
+            "edge_assembly" => Ok(Crossover::EdgeAssembly),
+            _ => Err(format!("Unknown crossover: {}", name)),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl<'source> FromPyObject<'source> for Crossover {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        let name: String = ob.extract()?;
+        name.parse().map_err(PyValueError::new_err)
+    }
+}
+
+impl Crossover {
+    pub fn cross<C: Cost>(
+        &self,
+        first: &Solution,
+        second: &Solution,
+        dmatrix: &DistanceMatrix<C>,
+    ) -> Solution {
+        assert_eq!(first.size, second.size);
+        match self {
+            Crossover::Order => order_crossover(first, second),
+            Crossover::EdgeRecombination => edge_recombination(first, second, dmatrix),
+            Crossover::EdgeAssembly => edge_assembly(first, second, dmatrix),
+        }
+    }
+}
+
+/// Successor of every city along the tour
+fn successors(solution: &Solution) -> Vec<usize> {
+    let mut next = vec![0; solution.size];
+    for (i, &city) in solution.path.iter().enumerate() {
+        next[city] = solution.path[(i + 1) % solution.size];
+    }
+    next
+}
+
+/// The tour of a successor array describing a single cycle
+fn from_successors(next: &[usize]) -> Solution {
+    let mut path = Vec::with_capacity(next.len());
+    let mut city = 0;
+    for _ in 0..next.len() {
+        path.push(city);
+        city = next[city];
+    }
+    Solution::new(path)
+}
+
+fn order_crossover(first: &Solution, second: &Solution) -> Solution {
+    let n = first.size;
+    let rng = &mut rng();
+    let start = rng.gen_range(0..n);
+    let length = rng.gen_range(1..n);
+    let mut taken = vec![false; n];
+    let mut path = Vec::with_capacity(n);
+    for i in start..start + length {
+        let city = first.path[i % n];
+        taken[city] = true;
+        path.push(city);
+    }
+    // The rest in the order of the second parent, from where the segment ends
+    for i in start + length..start + length + n {
+        let city = second.path[i % n];
+        if !taken[city] {
+            taken[city] = true;
+            path.push(city);
+        }
+    }
+    Solution::new(path)
+}
+
+fn edge_recombination<C: Cost>(
+    first: &Solution,
+    second: &Solution,
+    dmatrix: &DistanceMatrix<C>,
+) -> Solution {
+    let n = first.size;
+    let arcs = [successors(first), successors(second)];
+    let mut visited = vec![false; n];
+    let unvisited_successors = |city: usize, visited: &[bool]| {
+        let (a, b) = (arcs[0][city], arcs[1][city]);
+        (!visited[a]) as usize + (a != b && !visited[b]) as usize
+    };
+
+    let mut city = first.path[0];
+    let mut path = Vec::with_capacity(n);
+    loop {
+        visited[city] = true;
+        path.push(city);
+        if path.len() == n {
+            break;
+        }
+        let next = [arcs[0][city], arcs[1][city]]
+            .into_iter()
+            .filter(|&next| !visited[next])
+            .min_by(|&a, &b| {
+                unvisited_successors(a, &visited)
+                    .cmp(&unvisited_successors(b, &visited))
+                    .then(dmatrix.cost(city, a).total_cmp(&dmatrix.cost(city, b)))
+            });
+        // A dead end continues at the closest city left
+        city = next.unwrap_or_else(|| {
+            (0..n)
+                .filter(|&next| !visited[next])
+                .min_by(|&a, &b| dmatrix.cost(city, a).total_cmp(&dmatrix.cost(city, b)))
+                .unwrap()
+        });
+    }
+    Solution::new(path)
+}
+
+/// Cycles alternating an arc of the first parent, forwards, with an arc of
+/// the second, backwards; each is a list of the second parent's arcs
+fn ab_cycles(first: &[usize], second: &[usize]) -> Vec<Vec<(usize, usize)>> {
+    let n = first.len();
+    let mut second_predecessor = vec![0; n];
+    for (city, &next) in second.iter().enumerate() {
+        second_predecessor[next] = city;
+    }
+    let mut used = vec![false; n];
+    let mut cycles = vec![];
+    for start in 0..n {
+        if used[start] || first[start] == second[start] {
+            continue;
+        }
+        let mut cycle = vec![];
+        let mut tail = start;
+        loop {
+            used[tail] = true;
+            let head = first[tail];
+            let back = second_predecessor[head];
+            cycle.push((back, head));
+            tail = back;
+            if tail == start {
+                break;
+            }
+        }
+        cycles.push(cycle);
+    }
+    cycles
+}
+
+fn edge_assembly<C: Cost>(
+    first: &Solution,
+    second: &Solution,
+    dmatrix: &DistanceMatrix<C>,
+) -> Solution {
+    let first_next = successors(first);
+    let mut cycles = ab_cycles(&first_next, &successors(second));
+    if cycles.is_empty() {
+        return first.clone();
+    }
+    cycles.shuffle(&mut rng());
+    cycles
+        .iter()
+        .take(ASSEMBLY_TRIALS)
+        .map(|cycle| {
+            let mut next = first_next.clone();
+            for &(tail, head) in cycle.iter() {
+                next[tail] = head;
+            }
+            let offspring = from_successors(&merge_subtours(next, dmatrix));
+            let cost = offspring.cost(dmatrix);
+            (offspring, cost)
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(offspring, _)| offspring)
+        .unwrap()
+}
+
+/// Joins the cycles of a successor array into one, every time merging the
+/// smallest cycle through the cheapest exchange of two arcs
+fn merge_subtours<C: Cost>(mut next: Vec<usize>, dmatrix: &DistanceMatrix<C>) -> Vec<usize> {
+    let n = next.len();
+    let mut label = vec![usize::MAX; n];
+    let mut members: Vec<Vec<usize>> = vec![];
+    for start in 0..n {
+        if label[start] != usize::MAX {
+            continue;
+        }
+        let mut cycle = vec![];
+        let mut city = start;
+        while label[city] == usize::MAX {
+            label[city] = members.len();
+            cycle.push(city);
+            city = next[city];
+        }
+        members.push(cycle);
+    }
+    let mut previous = vec![0; n];
+    for (city, &successor) in next.iter().enumerate() {
+        previous[successor] = city;
+    }
+
+    let mut alive: Vec<usize> = (0..members.len()).collect();
+    while alive.len() > 1 {
+        let (position, &smallest) = alive
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &cycle)| members[cycle].len())
+            .unwrap();
+        // Exchanging a -> a' and b -> b' for a -> b' and b -> a'
+        let gain = |a: usize, b_next: usize, next: &[usize], previous: &[usize]| {
+            let (a_next, b) = (next[a], previous[b_next]);
+            (dmatrix.cost(a, b_next) + dmatrix.cost(b, a_next))
+                - (dmatrix.cost(a, a_next) + dmatrix.cost(b, b_next))
+        };
+        let consider = |best: &mut Option<(C, usize, usize)>, a: usize, b_next: usize| {
+            if label[b_next] == smallest {
+                return;
+            }
+            let delta = gain(a, b_next, &next, &previous);
+            if best.is_none_or(|(cost, _, _)| delta < cost) {
+                *best = Some((delta, a, b_next));
+            }
+        };
+        let mut best = None;
+        for &a in members[smallest].iter() {
+            if let Some(lists) = &dmatrix.neighbours {
+                for &b_next in lists.successors[a].iter() {
+                    consider(&mut best, a, b_next);
+                }
+            }
+        }
+        // None of the candidates leaves the cycle
+        if best.is_none() {
+            for &a in members[smallest].iter() {
+                for b_next in 0..n {
+                    consider(&mut best, a, b_next);
+                }
+            }
+        }
+
+        let (_, a, b_next) = best.expect("Another cycle is left to merge with");
+        let (a_next, b) = (next[a], previous[b_next]);
+        next[a] = b_next;
+        previous[b_next] = a;
+        next[b] = a_next;
+        previous[a_next] = b;
+        let target = label[b_next];
+        for city in std::mem::take(&mut members[smallest]) {
+            label[city] = target;
+            members[target].push(city);
+        }
+        alive.swap_remove(position);
+    }
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::utils::neighbours::NeighbourRanking;
+
+    fn matrix(n: usize) -> DistanceMatrix<i64> {
+        DistanceMatrix::modular(n, [17, 31, 1, 29])
+    }
+
+    #[test]
+    fn test_identical_parents() {
+        let dmatrix = matrix(9);
+        let parent = Solution::new(vec![3, 1, 4, 0, 5, 8, 2, 6, 7]);
+        let offspring = Crossover::EdgeAssembly.cross(&parent, &parent, &dmatrix);
+        assert_eq!(offspring.path, parent.path);
+        let offspring = Crossover::EdgeRecombination.cross(&parent, &parent, &dmatrix);
+        assert_eq!(successors(&offspring), successors(&parent));
+    }
+
+    #[test]
+    fn test_ab_cycles_cover_the_difference() {
+        let first = successors(&Solution::new(vec![0, 1, 2, 3, 4, 5]));
+        let second = successors(&Solution::new(vec![0, 2, 1, 3, 5, 4]));
+        let cycles = ab_cycles(&first, &second);
+        let arcs: usize = cycles.iter().map(|cycle| cycle.len()).sum();
+        let differing = (0..6).filter(|&city| first[city] != second[city]).count();
+        assert_eq!(arcs, differing);
+        for &(tail, head) in cycles.iter().flatten() {
+            assert_eq!(second[tail], head);
+        }
+    }
+
+    fn permutation(n: usize) -> impl Strategy<Value = Vec<usize>> {
+        Just((0..n).collect::<Vec<usize>>()).prop_shuffle()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_offspring_are_tours(
+            first in permutation(14),
+            second in permutation(14),
+            operator in prop_oneof![
+                Just(Crossover::Order),
+                Just(Crossover::EdgeRecombination),
+                Just(Crossover::EdgeAssembly),
+            ],
+        ) {
+            let mut dmatrix = matrix(14);
+            dmatrix.compute_neighbours(3, NeighbourRanking::Cost);
+            let offspring = operator.cross(&Solution::new(first), &Solution::new(second), &dmatrix);
+            let mut path = offspring.path.clone();
+            path.sort();
+            prop_assert_eq!(path, (0..14).collect::<Vec<usize>>());
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::time::Instant;
+
+use super::{
+    assignment::solve_assignment_costs,
+    cost::Cost,
+    matrix::DistanceMatrix,
+    solution::{ComputedSolution, Solution},
+};
+
+/// Largest instance solved with the Held-Karp dynamic programming,
+/// above it the tables would take gigabytes
+pub const HELD_KARP_MAX_SIZE: usize = 20;
+
+/// Solves the instance to optimality, picking Held-Karp for tiny instances
+/// and branch and bound for the rest. `None` when the branch and bound is
+/// not done by the deadline, or has none to run against.
+pub fn solve_exact<C: Cost>(
+    dmatrix: &DistanceMatrix<C>,
+    deadline: Option<Instant>,
+) -> Option<ComputedSolution<C>> {
+    if dmatrix.size <= HELD_KARP_MAX_SIZE {
+        Some(held_karp(dmatrix))
+    } else {
+        deadline.and_then(|deadline| branch_and_bound(dmatrix, deadline))
+    }
+}
+
+/// Held-Karp dynamic programming over subsets, O(2^n * n^2) time.
+/// The tour is anchored at city 0 and subsets are taken over the other cities.
+pub fn held_karp<C: Cost>(dmatrix: &DistanceMatrix<C>) -> ComputedSolution<C> {
+    let n = dmatrix.size;
+    assert!(n <= HELD_KARP_MAX_SIZE, "Instance too big for Held-Karp");
+    if n < 3 {
+        let solution = Solution::new((0..n).collect());
+        let cost = solution.cost(dmatrix);
+        return ComputedSolution { solution, cost };
+    }
+
+    // best[mask * m + j] is the cheapest path from 0 through `mask` ending in city j + 1
+    let m = n - 1;
+    let subsets = 1usize << m;
+    let mut best = vec![C::INFINITY; subsets * m];
+    let mut parent = vec![u8::MAX; subsets * m];
+    for j in 0..m {
+        best[(1 << j) * m + j] = dmatrix.cost(0, j + 1);
+    }
+    for mask in 1..subsets {
+        for j in 0..m {
+            if mask & (1 << j) == 0 {
+                continue;
+            }
+            let previous_mask = mask ^ (1 << j);
+            if previous_mask == 0 {
+                continue;
+            }
+            let mut value = C::INFINITY;
+            let mut from = u8::MAX;
+            for k in 0..m {
+                if previous_mask & (1 << k) == 0 {
+                    continue;
+                }
+                let candidate = best[previous_mask * m + k] + dmatrix.cost(k + 1, j + 1);
+                if candidate < value {
+                    value = candidate;
+                    from = k as u8;
+                }
+            }
+            best[mask * m + j] = value;
+            parent[mask * m + j] = from;
+        }
+    }
+
+    let full = subsets - 1;
+    let (mut last, cost) = (0..m)
+        .map(|j| (j, best[full * m + j] + dmatrix.cost(j + 1, 0)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+
+    // Walk the parents back from the last city
+    let mut path = Vec::with_capacity(n);
+    let mut mask = full;
+    loop {
+        path.push(last + 1);
+        let from = parent[mask * m + last];
+        mask ^= 1 << last;
+        if from == u8::MAX {
+            break;
+        }
+        last = from as usize;
+    }
+    path.push(0);
+    path.reverse();
+    ComputedSolution {
+        solution: Solution::new(path),
+        cost,
+    }
+}
+
+/// Depth-first branch and bound with the assignment problem relaxation as
+/// the bound. Subtours are broken with the Carpaneto-Toth scheme: the arcs of
+/// the shortest subtour are excluded one at a time, each child also fixing
+/// the previously excluded arcs of the subtour. The search tree can grow
+/// exponentially, so it gives up with `None` once the deadline passes.
+pub fn branch_and_bound<C: Cost>(
+    dmatrix: &DistanceMatrix<C>,
+    deadline: Instant,
+) -> Option<ComputedSolution<C>> {
+    if dmatrix.size < 3 {
+        return Some(held_karp(dmatrix));
+    }
+
+    let initial = Solution::karp_patching_solution(dmatrix);
+    let mut best = ComputedSolution {
+        cost: initial.cost(dmatrix),
+        solution: initial,
+    };
+
+    let n = dmatrix.size;
+    let mut stack: Vec<Vec<f64>> = vec![dmatrix.to_f64()];
+    while let Some(costs) = stack.pop() {
+        if Instant::now() >= deadline {
+            return None;
+        }
+        let assignment = match solve_assignment_costs(&costs, n) {
+            Some(assignment) => assignment,
+            None => continue,
+        };
+        if assignment.cost >= best.cost.to_f64() {
+            continue;
+        }
+        let mut cycles = assignment.cycles();
+        if cycles.len() == 1 {
+            let solution = Solution::new(cycles.pop().unwrap());
+            best = ComputedSolution {
+                cost: solution.cost(dmatrix),
+                solution,
+            };
+            continue;
+        }
+
+        let subtour = cycles.into_iter().min_by_key(|cycle| cycle.len()).unwrap();
+        let arcs: Vec<(usize, usize)> = subtour
+            .iter()
+            .map(|&city| (city, assignment.successors[city]))
+            .collect();
+        let mut children = Vec::with_capacity(arcs.len());
+        for (h, &(from, to)) in arcs.iter().enumerate() {
+            let mut child = costs.clone();
+            child[from * n + to] = f64::INFINITY;
+            for &(fixed_from, fixed_to) in arcs.iter().take(h) {
+                for city in 0..n {
+                    if city != fixed_from {
+                        child[city * n + fixed_to] = f64::INFINITY;
+                    }
+                    if city != fixed_to {
+                        child[fixed_from * n + city] = f64::INFINITY;
+                    }
+                }
+            }
+            children.push(child);
+        }
+        // The first child is explored first
+        stack.extend(children.into_iter().rev());
+    }
+    Some(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn brute_force(dmatrix: &DistanceMatrix) -> f64 {
+        fn permute(rest: &mut Vec<usize>, path: &mut Vec<usize>, d: &DistanceMatrix) -> f64 {
+            if rest.is_empty() {
+                return Solution::new(path.clone()).cost(d);
+            }
+            let mut best = f64::INFINITY;
+            for i in 0..rest.len() {
+                let city = rest.remove(i);
+                path.push(city);
+                best = f64::min(best, permute(rest, path, d));
+                path.pop();
+                rest.insert(i, city);
+            }
+            best
+        }
+        let mut rest: Vec<usize> = (1..dmatrix.size).collect();
+        permute(&mut rest, &mut vec![0], dmatrix)
+    }
+
+    fn asymmetric_matrix(size: usize) -> DistanceMatrix {
+        DistanceMatrix::modular(size, [31, 17, 7, 23])
+    }
+
+    #[test]
+    fn test_held_karp_is_optimal() {
+        let dmatrix = asymmetric_matrix(8);
+        let result = held_karp(&dmatrix);
+        assert_eq!(result.cost, brute_force(&dmatrix));
+        assert_eq!(result.solution.cost(&dmatrix), result.cost);
+        assert_eq!(result.solution.path[0], 0);
+    }
+
+    #[test]
+    fn test_held_karp_on_integers() {
+        let dmatrix = asymmetric_matrix(7);
+        let integers =
+            DistanceMatrix::from_flat(dmatrix.data.iter().map(|&cost| cost as i32).collect(), 7);
+        assert_eq!(held_karp(&integers).cost as f64, held_karp(&dmatrix).cost);
+    }
+
+    #[test]
+    fn test_branch_and_bound_matches_held_karp() {
+        let dmatrix = asymmetric_matrix(11);
+        let exact = held_karp(&dmatrix);
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let result = branch_and_bound(&dmatrix, deadline).unwrap();
+        assert_eq!(result.cost, exact.cost);
+        assert_eq!(result.solution.cost(&dmatrix), result.cost);
+    }
+
+    #[test]
+    fn test_branch_and_bound_stops_at_the_deadline() {
+        let dmatrix = asymmetric_matrix(30);
+        assert!(branch_and_bound(&dmatrix, Instant::now()).is_none());
+        assert!(solve_exact(&dmatrix, None).is_none());
+        assert!(solve_exact(&asymmetric_matrix(9), None).is_some());
+    }
+}
@@ -0,0 +1,537 @@
+use std::cmp::Ordering;
+
+use super::{
+    cost::Cost, matrix::DistanceMatrix, moves::Move, solution::Solution,
+    validation::ValidationError,
+};
+
+/// Vehicles leaving from and returning to a depot, splitting the cities into
+/// routes. The search works on a giant tour of the `expanded` instance, in
+/// which copies of the depot delimit the routes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fleet {
+    /// Routes at most, unused vehicles are empty routes
+    pub vehicles: usize,
+    pub depot: usize,
+    /// Largest load of a route, unlimited when not set
+    pub capacity: Option<f64>,
+    /// Demand of every city, the depot's is ignored; may be empty without
+    /// a capacity. In the expanded instance there is one for every city of
+    /// the original one and the depot copies come after them.
+    pub demands: Vec<f64>,
+}
+
+impl Fleet {
+    pub fn new(vehicles: usize, depot: usize) -> Self {
+        Fleet {
+            vehicles,
+            depot,
+            capacity: None,
+            demands: vec![],
+        }
+    }
+
+    pub fn with_capacity(self, capacity: f64, demands: Vec<f64>) -> Self {
+        Fleet {
+            capacity: Some(capacity),
+            demands,
+            ..self
+        }
+    }
+
+    pub fn validate(&self, size: usize) -> Result<(), ValidationError> {
+        let invalid = |message: String| Err(ValidationError::Matrix(message));
+        if self.vehicles == 0 {
+            return invalid("the fleet needs at least one vehicle".to_string());
+        }
+        if self.depot >= size {
+            return invalid(format!(
+                "the depot {} is out of the {} cities",
+                self.depot, size
+            ));
+        }
+        if (self.capacity.is_some() || !self.demands.is_empty()) && self.demands.len() != size {
+            return invalid(format!(
+                "demands are given for {} cities, the matrix has {}",
+                self.demands.len(),
+                size
+            ));
+        }
+        if let Some(capacity) = self
+            .capacity
+            .filter(|&capacity| !capacity.is_finite() || capacity <= 0.0)
+        {
+            return invalid(format!("the vehicle capacity {} is not positive", capacity));
+        }
+        if let Some((city, demand)) = self
+            .demands
+            .iter()
+            .enumerate()
+            .find(|(_, &demand)| !demand.is_finite() || demand < 0.0)
+        {
+            return invalid(format!("city {} has the demand {}", city, demand));
+        }
+        Ok(())
+    }
+
+    /// The giant tour instance: `vehicles - 1` copies of the depot after the
+    /// cities, every arc between two of the depots free, so that a tour is
+    /// the routes one after the other
+    pub fn expanded<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> DistanceMatrix<C> {
+        let n = dmatrix.size;
+        let size = n + self.vehicles - 1;
+        let original = |city: usize| if city >= n { self.depot } else { city };
+        let is_depot = |city: usize| city >= n || city == self.depot;
+        let mut data = Vec::with_capacity(size * size);
+        for from in 0..size {
+            data.extend((0..size).map(|to| {
+                if from != to && is_depot(from) && is_depot(to) {
+                    C::ZERO
+                } else {
+                    dmatrix.cost(original(from), original(to))
+                }
+            }));
+        }
+        let mut demands = self.demands.clone();
+        demands.resize(n, 0.0);
+        DistanceMatrix {
+            fleet: Some(Fleet {
+                demands,
+                ..self.clone()
+            }),
+            ..DistanceMatrix::from_flat(data, size)
+        }
+    }
+
+    /// The depot or one of its copies in the expanded instance
+    #[inline]
+    pub fn is_depot(&self, city: usize) -> bool {
+        city == self.depot || city >= self.demands.len()
+    }
+
+    #[inline]
+    fn demand(&self, city: usize) -> f64 {
+        if self.is_depot(city) {
+            0.0
+        } else {
+            self.demands[city]
+        }
+    }
+
+    /// The routes of a giant tour of the expanded instance, every vehicle's
+    /// cities in order without the depot, empty for an unused vehicle
+    pub fn routes(&self, solution: &Solution) -> Vec<Vec<usize>> {
+        let n = solution.size;
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == self.depot)
+            .unwrap();
+        let mut routes = vec![vec![]];
+        for k in 1..n {
+            let city = solution.path[(at + k) % n];
+            if self.is_depot(city) {
+                routes.push(vec![]);
+            } else {
+                routes.last_mut().unwrap().push(city);
+            }
+        }
+        routes
+    }
+
+    /// The giant tour of the expanded instance driving the routes in order,
+    /// unused vehicles after them
+    pub fn giant_tour(&self, routes: &[Vec<usize>]) -> Solution {
+        assert!(routes.len() <= self.vehicles);
+        let cities = self.demands.len();
+        let mut path = vec![self.depot];
+        for (k, route) in routes.iter().enumerate() {
+            if k > 0 {
+                path.push(cities + k - 1);
+            }
+            path.extend(route);
+        }
+        path.extend(cities + routes.len().max(1) - 1..cities + self.vehicles - 1);
+        Solution::new(path)
+    }
+
+    /// The routes one after the other as a tour of the original instance,
+    /// each starting at the depot, which thus repeats; unused vehicles are
+    /// left out
+    pub fn tour(&self, routes: &[Vec<usize>]) -> Solution {
+        let mut path = vec![];
+        for route in routes.iter().filter(|route| !route.is_empty()) {
+            path.push(self.depot);
+            path.extend(route);
+        }
+        if path.is_empty() {
+            path.push(self.depot);
+        }
+        Solution::new(path)
+    }
+
+    /// Total demand of each route
+    pub fn loads(&self, routes: &[Vec<usize>]) -> Vec<f64> {
+        routes
+            .iter()
+            .map(|route| route.iter().map(|&city| self.demand(city)).sum())
+            .collect()
+    }
+
+    /// Demand over the capacity summed over the routes
+    pub fn overload(&self, routes: &[Vec<usize>]) -> f64 {
+        let Some(capacity) = self.capacity else {
+            return 0.0;
+        };
+        self.loads(routes)
+            .iter()
+            .map(|load| f64::max(load - capacity, 0.0))
+            .sum()
+    }
+
+    /// Optimal split of an order of the cities into at most `vehicles`
+    /// consecutive routes: the least overload, then the least cost. A
+    /// shortest path over the ends of the routes, in `O(vehicles * n^2)`.
+    pub fn split<C: Cost>(&self, dmatrix: &DistanceMatrix<C>, order: &[usize]) -> Vec<Vec<usize>> {
+        let cities: Vec<usize> = order
+            .iter()
+            .copied()
+            .filter(|&city| !self.is_depot(city))
+            .collect();
+        let k = cities.len();
+        let depot = self.depot;
+        let capacity = self.capacity.unwrap_or(f64::INFINITY);
+        // Overload and cost of the best split of the first `j` cities into
+        // `r` routes, with where the last route starts
+        let unreachable = (f64::INFINITY, f64::INFINITY, 0);
+        let mut best = vec![vec![unreachable; k + 1]; self.vehicles + 1];
+        best[0][0] = (0.0, 0.0, 0);
+        for r in 1..=self.vehicles {
+            for j in 1..=k {
+                let mut load = 0.0;
+                let mut inner = 0.0;
+                for i in (0..j).rev() {
+                    // The route drives cities[i..j]
+                    load += self.demand(cities[i]);
+                    if i + 1 < j {
+                        inner += dmatrix.cost(cities[i], cities[i + 1]).to_f64();
+                    }
+                    let (overload, cost, _) = best[r - 1][i];
+                    if overload.is_infinite() {
+                        continue;
+                    }
+                    let route = dmatrix.cost(depot, cities[i]).to_f64()
+                        + inner
+                        + dmatrix.cost(cities[j - 1], depot).to_f64();
+                    let candidate = (overload + f64::max(load - capacity, 0.0), cost + route, i);
+                    if lexicographic(&candidate, &best[r][j]) == Ordering::Less {
+                        best[r][j] = candidate;
+                    }
+                }
+            }
+        }
+        let mut r = (1..=self.vehicles)
+            .min_by(|&a, &b| lexicographic(&best[a][k], &best[b][k]))
+            .unwrap();
+        let mut routes = vec![];
+        let mut j = k;
+        while j > 0 {
+            let i = best[r][j].2;
+            routes.push(cities[i..j].to_vec());
+            j = i;
+            r -= 1;
+        }
+        routes.reverse();
+        routes
+    }
+
+    /// The same cities split anew, for a giant tour of the expanded instance
+    pub fn resplit<C: Cost>(&self, dmatrix: &DistanceMatrix<C>, solution: &Solution) -> Solution {
+        let order: Vec<usize> = self.routes(solution).concat();
+        self.giant_tour(&self.split(dmatrix, &order))
+    }
+}
+
+fn lexicographic(a: &(f64, f64, usize), b: &(f64, f64, usize)) -> Ordering {
+    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+}
+
+/// Loads of the routes of a replica's giant tour by offset from the depot,
+/// offset `n` being the way back to it. A move only changes the routes it
+/// touches: from the start of the route of its first changed position to
+/// the end of the route of its last.
+#[derive(Debug, Clone)]
+pub struct Loads {
+    /// Position of the depot in the path
+    depot_at: usize,
+    /// Load of the route so far at each offset, zero at the depots
+    loads: Vec<f64>,
+    /// Overload of the route ending at each offset, zero but at the depots
+    overloads: Vec<f64>,
+    /// Total overload of the routes
+    pub overload: f64,
+    /// Loads and overloads of the last proposal from `pending_from` on,
+    /// kept by `commit`
+    pending: Vec<(f64, f64)>,
+    pending_from: usize,
+    pending_depot_at: usize,
+    pending_overload: f64,
+}
+
+impl Loads {
+    pub fn new(fleet: &Fleet, solution: &Solution) -> Self {
+        let n = solution.size;
+        let mut loads = Loads {
+            depot_at: 0,
+            loads: vec![0.0; n + 1],
+            overloads: vec![0.0; n + 1],
+            overload: 0.0,
+            pending: Vec::with_capacity(n + 1),
+            pending_from: 0,
+            pending_depot_at: 0,
+            pending_overload: 0.0,
+        };
+        loads.recompute(fleet, solution);
+        loads.commit();
+        loads
+    }
+
+    /// Total overload after the move was applied, the new loads are kept
+    /// until the next proposal and take effect with `commit`
+    pub fn propose(&mut self, fleet: &Fleet, proposal: &Move, solution: &Solution) -> f64 {
+        let n = solution.size;
+        let changed = match *proposal {
+            Move::Swap {
+                first,
+                second,
+                length,
+            } => {
+                let (low, high) = (usize::min(first, second), usize::max(first, second));
+                let (a, b) = solution.find_swap_indices(low, high, length);
+                [(a, length), (b, length)]
+            }
+            Move::Shuffle { start, length } => [(start, usize::min(length, n)); 2],
+            Move::Choose { position, .. } => [(position, 1); 2],
+        };
+        if changed.iter().all(|&(_, length)| length == 0) {
+            self.pending.clear();
+            self.pending_depot_at = self.depot_at;
+            self.pending_overload = self.overload;
+            return self.overload;
+        }
+        let offset = |position: usize| (position + n - self.depot_at) % n;
+        // A window over the depot may have moved it, the loads are summed anew
+        if changed
+            .iter()
+            .any(|&(start, length)| offset(start) == 0 || offset(start) + length > n)
+        {
+            return self.recompute(fleet, solution);
+        }
+        let first = changed
+            .iter()
+            .map(|&(start, _)| offset(start))
+            .min()
+            .unwrap();
+        let last = changed
+            .iter()
+            .map(|&(start, length)| offset(start) + length - 1)
+            .max()
+            .unwrap();
+
+        let at = self.depot_at;
+        let capacity = fleet.capacity.unwrap_or(f64::INFINITY);
+        let mut load = self.loads[first - 1];
+        let mut change = 0.0;
+        self.pending.clear();
+        for k in first..=n {
+            let city = solution.path[(at + k) % n];
+            let overload = if fleet.is_depot(city) {
+                let overload = f64::max(load - capacity, 0.0);
+                load = 0.0;
+                overload
+            } else {
+                load += fleet.demand(city);
+                0.0
+            };
+            change += overload - self.overloads[k];
+            self.pending.push((load, overload));
+            // The next routes are as before
+            if k > last && fleet.is_depot(city) {
+                break;
+            }
+        }
+        self.pending_from = first;
+        self.pending_depot_at = at;
+        self.pending_overload = f64::max(self.overload + change, 0.0);
+        self.pending_overload
+    }
+
+    /// Keeps the loads of the last proposal
+    pub fn commit(&mut self) {
+        for (k, &(load, overload)) in self.pending.iter().enumerate() {
+            self.loads[self.pending_from + k] = load;
+            self.overloads[self.pending_from + k] = overload;
+        }
+        self.pending.clear();
+        self.depot_at = self.pending_depot_at;
+        self.overload = self.pending_overload;
+    }
+
+    fn recompute(&mut self, fleet: &Fleet, solution: &Solution) -> f64 {
+        let n = solution.size;
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == fleet.depot)
+            .unwrap();
+        let capacity = fleet.capacity.unwrap_or(f64::INFINITY);
+        let mut load = 0.0;
+        let mut total = 0.0;
+        self.pending.clear();
+        self.pending.push((0.0, 0.0));
+        for k in 1..=n {
+            let city = solution.path[(at + k) % n];
+            let overload = if fleet.is_depot(city) {
+                let overload = f64::max(load - capacity, 0.0);
+                load = 0.0;
+                overload
+            } else {
+                load += fleet.demand(city);
+                0.0
+            };
+            total += overload;
+            self.pending.push((load, overload));
+        }
+        self.pending_from = 0;
+        self.pending_depot_at = at;
+        self.pending_overload = total;
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn matrix(size: usize) -> DistanceMatrix<i64> {
+        DistanceMatrix::modular(size, [11, 5, 3, 19])
+    }
+
+    fn route_cost(dmatrix: &DistanceMatrix<i64>, depot: usize, route: &[usize]) -> i64 {
+        let mut stops = vec![depot];
+        stops.extend(route);
+        stops.push(depot);
+        stops
+            .windows(2)
+            .map(|arc| dmatrix.cost(arc[0], arc[1]))
+            .sum()
+    }
+
+    #[test]
+    fn test_giant_tour_costs_its_routes() {
+        let dmatrix = matrix(8);
+        let fleet = Fleet::new(3, 2);
+        let expanded = fleet.expanded(&dmatrix);
+        assert_eq!(expanded.size, 10);
+        let fleet = expanded.fleet.clone().unwrap();
+        let routes = vec![vec![0, 5], vec![1, 3, 7, 4], vec![6]];
+        let giant = fleet.giant_tour(&routes);
+        assert_eq!(fleet.routes(&giant), routes);
+        let total: i64 = routes
+            .iter()
+            .map(|route| route_cost(&dmatrix, 2, route))
+            .sum();
+        assert_eq!(giant.cost(&expanded), total);
+        assert_eq!(fleet.tour(&routes).path, vec![2, 0, 5, 2, 1, 3, 7, 4, 2, 6]);
+        // An unused vehicle costs nothing
+        let routes = vec![vec![0, 5, 1, 3], vec![7, 4, 6]];
+        let giant = fleet.giant_tour(&routes);
+        assert_eq!(fleet.routes(&giant)[..2], routes[..]);
+        assert!(fleet.routes(&giant)[2].is_empty());
+    }
+
+    #[test]
+    fn test_split_is_optimal() {
+        let dmatrix = matrix(9);
+        let demands = vec![0.0, 3.0, 4.0, 2.0, 5.0, 1.0, 3.0, 4.0, 2.0];
+        let fleet = Fleet::new(3, 0).with_capacity(9.0, demands);
+        let order = [4, 1, 7, 2, 8, 3, 6, 5];
+        let routes = fleet.split(&dmatrix, &order);
+        assert_eq!(routes.concat(), order);
+        assert!(routes.len() <= 3);
+        let cost = |routes: &[Vec<usize>]| -> i64 {
+            routes
+                .iter()
+                .map(|route| route_cost(&dmatrix, 0, route))
+                .sum()
+        };
+        // Every way of cutting the order into up to three routes
+        let mut best = (f64::INFINITY, i64::MAX);
+        for first in 1..=order.len() {
+            for second in first..=order.len() {
+                let cuts = [
+                    order[..first].to_vec(),
+                    order[first..second].to_vec(),
+                    order[second..].to_vec(),
+                ];
+                let cuts: Vec<Vec<usize>> = cuts.into_iter().filter(|r| !r.is_empty()).collect();
+                let candidate = (fleet.overload(&cuts), cost(&cuts));
+                if candidate.0 < best.0 || candidate.0 == best.0 && candidate.1 < best.1 {
+                    best = candidate;
+                }
+            }
+        }
+        assert_eq!((fleet.overload(&routes), cost(&routes)), best);
+    }
+
+    #[test]
+    fn test_rejects_bad_fleets() {
+        assert!(Fleet::new(0, 0).validate(5).is_err());
+        assert!(Fleet::new(2, 5).validate(5).is_err());
+        assert!(Fleet::new(2, 0)
+            .with_capacity(10.0, vec![1.0; 4])
+            .validate(5)
+            .is_err());
+        assert!(Fleet::new(2, 0)
+            .with_capacity(10.0, vec![1.0; 5])
+            .validate(5)
+            .is_ok());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_incremental_matches_full_loads(
+            moves in prop::collection::vec(
+                (any::<bool>(), 0usize..16, 0usize..16, 0usize..=8, any::<bool>()),
+                1..20,
+            ),
+            demands in prop::collection::vec(0.0f64..10.0, 12),
+        ) {
+            // 12 cities and 5 vehicles, the copies of depot 0 are 12 to 15
+            let fleet = Fleet::new(5, 0).with_capacity(15.0, demands);
+            let fleet = fleet.expanded(&matrix(12)).fleet.unwrap();
+            let overload = |solution: &Solution| fleet.overload(&fleet.routes(solution));
+            let mut solution = Solution::new((0..16).collect());
+            let mut loads = Loads::new(&fleet, &solution);
+            let mut backup = vec![];
+            for (shuffle, first, second, length, accept) in moves {
+                let proposal = if shuffle {
+                    Move::Shuffle { start: first, length: 2 * length }
+                } else {
+                    Move::Swap { first, second, length }
+                };
+                proposal.apply(&mut solution, &mut backup);
+                let proposed = loads.propose(&fleet, &proposal, &solution);
+                prop_assert!((proposed - overload(&solution)).abs() < 1e-6);
+                if accept {
+                    loads.commit();
+                } else {
+                    proposal.undo(&mut solution, &backup);
+                }
+                prop_assert!((loads.overload - overload(&solution)).abs() < 1e-6);
+            }
+        }
+    }
+}
@@ -1,13 +1,55 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use rand::Rng;
+
+use super::random::rng;
+
 // metropolis_transition.py
 pub fn acceptance(solution_length: f64, new_solution_length: f64, temperature: f64) -> bool {
     let acceptance_probability = f64::exp(-(new_solution_length - solution_length) / temperature);
-    rand::random::<f64>() < f64::min(1.0, acceptance_probability)
+    rng().gen::<f64>() < f64::min(1.0, acceptance_probability)
+}
+
+/// `task(0)` to `task(count - 1)` on a pool of `jobs` threads, results in order
+pub fn parallel_map<T: Send>(
+    jobs: usize,
+    count: usize,
+    task: impl Fn(usize) -> T + Sync,
+) -> Vec<T> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..count).map(|_| None).collect::<Vec<Option<T>>>());
+    thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, count.max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= count {
+                    break;
+                }
+                let result = task(i);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("Every task is done once the scope ends"))
+        .collect()
 }
 
+#[cfg(test)]
 pub trait CountUnique {
     fn unique(self) -> usize;
 }
 
+#[cfg(test)]
 impl<I, T> CountUnique for I
 where
     I: Iterator<Item = T>,
@@ -0,0 +1,413 @@
+use std::str::FromStr;
+
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, prelude::*};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    assignment::solve_assignment, cost::Cost, matrix::DistanceMatrix, random::rng,
+    solution::Solution,
+};
+
+/// Construction heuristics used to seed the initial replicas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Heuristic {
+    NearestNeighbor,
+    BackwardNearestNeighbor,
+    GreedyEdge,
+    CheapestInsertion,
+    NearestInsertion,
+    FarthestInsertion,
+    RandomInsertion,
+    KarpPatching,
+}
+
+impl FromStr for Heuristic {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "nearest_neighbor" => Ok(Heuristic::NearestNeighbor),
+            "backward_nearest_neighbor" => Ok(Heuristic::BackwardNearestNeighbor),
+            "greedy_edge" => Ok(Heuristic::GreedyEdge),
+            "cheapest_insertion" => Ok(Heuristic::CheapestInsertion),
+            "nearest_insertion" => Ok(Heuristic::NearestInsertion),
+            "farthest_insertion" => Ok(Heuristic::FarthestInsertion),
+            "random_insertion" => Ok(Heuristic::RandomInsertion),
+            "karp_patching" => Ok(Heuristic::KarpPatching),
+            _ => Err(format!("Unknown heuristic: {}", name)),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl<'source> FromPyObject<'source> for Heuristic {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        let name: String = ob.extract()?;
+        name.parse().map_err(PyValueError::new_err)
+    }
+}
+
+impl Heuristic {
+    /// Builds every tour this heuristic can produce on the given matrix.
+    /// Start-city dependent heuristics return one tour per starting city.
+    pub fn solutions<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> Vec<Solution> {
+        let starting_cities = 0..dmatrix.size;
+        match self {
+            Heuristic::NearestNeighbor => starting_cities
+                .map(|city| Solution::nearest_neightbor_solution(dmatrix, city))
+                .collect(),
+            Heuristic::BackwardNearestNeighbor => starting_cities
+                .map(|city| Solution::backwards_nearest_neightbor_solution(dmatrix, city))
+                .collect(),
+            Heuristic::GreedyEdge => vec![Solution::greedy_edge_solution(dmatrix)],
+            Heuristic::CheapestInsertion => starting_cities
+                .map(|city| Solution::cheapest_insertion_solution(dmatrix, city))
+                .collect(),
+            Heuristic::NearestInsertion => starting_cities
+                .map(|city| Solution::nearest_insertion_solution(dmatrix, city, false))
+                .collect(),
+            Heuristic::FarthestInsertion => starting_cities
+                .map(|city| Solution::nearest_insertion_solution(dmatrix, city, true))
+                .collect(),
+            Heuristic::RandomInsertion => starting_cities
+                .map(|city| Solution::random_insertion_solution(dmatrix, city))
+                .collect(),
+            Heuristic::KarpPatching => vec![Solution::karp_patching_solution(dmatrix)],
+        }
+    }
+}
+
+/// A partial tour stored as a successor list, so that inserting
+/// a city after a given one is O(1)
+struct InsertionTour<'a, C: Cost> {
+    dmatrix: &'a DistanceMatrix<C>,
+    next: Vec<usize>,
+    members: Vec<usize>,
+    in_tour: Vec<bool>,
+}
+
+impl<'a, C: Cost> InsertionTour<'a, C> {
+    fn new(dmatrix: &'a DistanceMatrix<C>, starting_city: usize) -> Self {
+        if starting_city >= dmatrix.size {
+            panic!("Impossible choice for the first city")
+        }
+        let mut next = vec![usize::MAX; dmatrix.size];
+        let mut in_tour = vec![false; dmatrix.size];
+        next[starting_city] = starting_city;
+        in_tour[starting_city] = true;
+        InsertionTour {
+            dmatrix,
+            next,
+            members: vec![starting_city],
+            in_tour,
+        }
+    }
+
+    #[inline]
+    fn insertion_delta(&self, tail: usize, city: usize) -> C {
+        let head = self.next[tail];
+        let dmatrix = self.dmatrix;
+        if head == tail {
+            // Closing a single city tour, there is no edge to remove
+            return dmatrix.cost(tail, city) + dmatrix.cost(city, tail);
+        }
+        dmatrix.cost(tail, city) + dmatrix.cost(city, head) - dmatrix.cost(tail, head)
+    }
+
+    fn cheapest_position(&self, city: usize) -> (usize, C) {
+        self.members
+            .iter()
+            .map(|&tail| (tail, self.insertion_delta(tail, city)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap()
+    }
+
+    fn insert_after(&mut self, tail: usize, city: usize) {
+        self.next[city] = self.next[tail];
+        self.next[tail] = city;
+        self.in_tour[city] = true;
+        self.members.push(city);
+    }
+
+    fn into_solution(self) -> Solution {
+        let start = self.members[0];
+        let mut path = Vec::with_capacity(self.members.len());
+        let mut city = start;
+        loop {
+            path.push(city);
+            city = self.next[city];
+            if city == start {
+                break;
+            }
+        }
+        Solution::new(path)
+    }
+}
+
+impl Solution {
+    /// Asymmetric greedy matching: repeatedly takes the cheapest arc that
+    /// keeps every city with at most one successor and one predecessor
+    /// and does not close a subtour
+    pub fn greedy_edge_solution<C: Cost>(dmatrix: &DistanceMatrix<C>) -> Self {
+        let n = dmatrix.size;
+        if n == 1 {
+            return Solution::new(vec![0]);
+        }
+        let mut arcs: Vec<(usize, usize)> = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .collect();
+        arcs.sort_by(|&(a, b), &(c, d)| dmatrix.cost(a, b).total_cmp(&dmatrix.cost(c, d)));
+
+        // Every fragment is a path, tracked by the head reached from each tail
+        let mut successor = vec![usize::MAX; n];
+        let mut has_predecessor = vec![false; n];
+        let mut fragment_end: Vec<usize> = (0..n).collect();
+        let mut fragment_start: Vec<usize> = (0..n).collect();
+        let mut added = 0;
+        for (from, to) in arcs {
+            if added == n - 1 {
+                break;
+            }
+            if successor[from] != usize::MAX || has_predecessor[to] {
+                continue;
+            }
+            // `from` is the end of its fragment and `to` the start of its own
+            let start = fragment_start[from];
+            let end = fragment_end[to];
+            if start == to {
+                continue;
+            }
+            successor[from] = to;
+            has_predecessor[to] = true;
+            fragment_end[start] = end;
+            fragment_start[end] = start;
+            added += 1;
+        }
+
+        let first = (0..n).find(|&city| !has_predecessor[city]).unwrap();
+        let mut path = Vec::with_capacity(n);
+        let mut city = first;
+        while city != usize::MAX {
+            path.push(city);
+            city = successor[city];
+        }
+        Solution::new(path)
+    }
+
+    /// Inserts, at every step, the city and position with the smallest increase
+    /// of the tour length. Each outside city remembers its best position, which is
+    /// only fully recomputed when the edge it pointed at gets broken.
+    pub fn cheapest_insertion_solution<C: Cost>(
+        dmatrix: &DistanceMatrix<C>,
+        starting_city: usize,
+    ) -> Self {
+        let mut tour = InsertionTour::new(dmatrix, starting_city);
+        let mut best: Vec<(usize, C)> = (0..dmatrix.size)
+            .map(|city| (starting_city, tour.insertion_delta(starting_city, city)))
+            .collect();
+
+        for _ in 1..dmatrix.size {
+            let (city, (tail, _)) = best
+                .iter()
+                .enumerate()
+                .filter(|(city, _)| !tour.in_tour[*city])
+                .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+                .map(|(city, position)| (city, *position))
+                .unwrap();
+            tour.insert_after(tail, city);
+
+            for (other, position) in best.iter_mut().enumerate() {
+                if tour.in_tour[other] {
+                    continue;
+                }
+                if position.0 == tail {
+                    *position = tour.cheapest_position(other);
+                    continue;
+                }
+                for new_tail in [tail, city] {
+                    let delta = tour.insertion_delta(new_tail, other);
+                    if delta < position.1 {
+                        *position = (new_tail, delta);
+                    }
+                }
+            }
+        }
+        tour.into_solution()
+    }
+
+    /// Nearest (or farthest) insertion: picks the outside city closest to
+    /// (or farthest from) the current tour and inserts it at its cheapest position
+    pub fn nearest_insertion_solution<C: Cost>(
+        dmatrix: &DistanceMatrix<C>,
+        starting_city: usize,
+        farthest: bool,
+    ) -> Self {
+        let closeness = |a: usize, b: usize| {
+            let (there, back) = (dmatrix.cost(a, b), dmatrix.cost(b, a));
+            if there < back {
+                there
+            } else {
+                back
+            }
+        };
+        let mut tour = InsertionTour::new(dmatrix, starting_city);
+        let mut distance_to_tour: Vec<C> = (0..dmatrix.size)
+            .map(|city| closeness(starting_city, city))
+            .collect();
+
+        for _ in 1..dmatrix.size {
+            let candidates = distance_to_tour
+                .iter()
+                .enumerate()
+                .filter(|(city, _)| !tour.in_tour[*city]);
+            let city = if farthest {
+                candidates.max_by(|(_, a), (_, b)| a.total_cmp(b))
+            } else {
+                candidates.min_by(|(_, a), (_, b)| a.total_cmp(b))
+            }
+            .map(|(city, _)| city)
+            .unwrap();
+            let (tail, _) = tour.cheapest_position(city);
+            tour.insert_after(tail, city);
+
+            for (other, distance) in distance_to_tour.iter_mut().enumerate() {
+                let through_city = closeness(city, other);
+                if through_city < *distance {
+                    *distance = through_city;
+                }
+            }
+        }
+        tour.into_solution()
+    }
+
+    /// Inserts the remaining cities in random order, each at its cheapest position
+    pub fn random_insertion_solution<C: Cost>(
+        dmatrix: &DistanceMatrix<C>,
+        starting_city: usize,
+    ) -> Self {
+        let mut tour = InsertionTour::new(dmatrix, starting_city);
+        let mut order: Vec<usize> = (0..dmatrix.size)
+            .filter(|&city| city != starting_city)
+            .collect();
+        order.shuffle(&mut rng());
+        for city in order {
+            let (tail, _) = tour.cheapest_position(city);
+            tour.insert_after(tail, city);
+        }
+        tour.into_solution()
+    }
+
+    /// Karp's patching: solves the assignment problem and then repeatedly
+    /// patches the cheapest-to-join cycle into the largest one
+    pub fn karp_patching_solution<C: Cost>(dmatrix: &DistanceMatrix<C>) -> Self {
+        if dmatrix.size < 2 {
+            return Solution::new((0..dmatrix.size).collect());
+        }
+        let assignment = solve_assignment(dmatrix);
+        let mut successors = assignment.successors.clone();
+        let mut cycles = assignment.cycles();
+        cycles.sort_by_key(|cycle| std::cmp::Reverse(cycle.len()));
+
+        while cycles.len() > 1 {
+            let mut best = (C::INFINITY, 0, 0, 0);
+            for &a in cycles[0].iter() {
+                let a_next = successors[a];
+                for (index, cycle) in cycles.iter().enumerate().skip(1) {
+                    for &c in cycle.iter() {
+                        let c_next = successors[c];
+                        let delta = dmatrix.cost(a, c_next) + dmatrix.cost(c, a_next)
+                            - dmatrix.cost(a, a_next)
+                            - dmatrix.cost(c, c_next);
+                        if delta < best.0 {
+                            best = (delta, a, c, index);
+                        }
+                    }
+                }
+            }
+            let (_, a, c, index) = best;
+            successors.swap(a, c);
+            let patched = cycles.swap_remove(index);
+            cycles[0].extend(patched);
+        }
+
+        let mut path = Vec::with_capacity(dmatrix.size);
+        let mut city = 0;
+        for _ in 0..dmatrix.size {
+            path.push(city);
+            city = successors[city];
+        }
+        Solution::new(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::helpers::CountUnique;
+
+    use super::*;
+
+    fn asymmetric_matrix(size: usize) -> DistanceMatrix {
+        DistanceMatrix::modular(size, [7, 13, 0, 17])
+    }
+
+    #[test]
+    fn test_every_heuristic_builds_a_permutation() {
+        let dmatrix = asymmetric_matrix(12);
+        let heuristics = [
+            Heuristic::NearestNeighbor,
+            Heuristic::BackwardNearestNeighbor,
+            Heuristic::GreedyEdge,
+            Heuristic::CheapestInsertion,
+            Heuristic::NearestInsertion,
+            Heuristic::FarthestInsertion,
+            Heuristic::RandomInsertion,
+            Heuristic::KarpPatching,
+        ];
+        for heuristic in heuristics {
+            let solutions = heuristic.solutions(&dmatrix);
+            assert!(!solutions.is_empty());
+            for solution in solutions {
+                assert_eq!(solution.path.len(), 12);
+                assert_eq!(solution.path.iter().unique(), 12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_greedy_edge_follows_cheap_cycle() {
+        // The cheap arcs form the cycle 0 -> 2 -> 1 -> 3 -> 0
+        let mut matrix = vec![vec![10.0; 4]; 4];
+        matrix[0][2] = 1.0;
+        matrix[2][1] = 1.0;
+        matrix[1][3] = 1.0;
+        matrix[3][0] = 1.0;
+        let dmatrix = DistanceMatrix::new(matrix);
+        let solution = Solution::greedy_edge_solution(&dmatrix);
+        assert_eq!(solution.cost(&dmatrix), 4.0);
+    }
+
+    #[test]
+    fn test_karp_patching_joins_cycles() {
+        let dmatrix = DistanceMatrix::new(vec![
+            vec![0.0, 1.0, 9.0, 9.0],
+            vec![1.0, 0.0, 2.0, 9.0],
+            vec![9.0, 9.0, 0.0, 1.0],
+            vec![2.0, 9.0, 1.0, 0.0],
+        ]);
+        let solution = Solution::karp_patching_solution(&dmatrix);
+        assert_eq!(solution.path.iter().unique(), 4);
+        assert_eq!(solution.cost(&dmatrix), 6.0);
+    }
+
+    #[test]
+    fn test_heuristic_from_name() {
+        assert_eq!(
+            "farthest_insertion".parse::<Heuristic>(),
+            Ok(Heuristic::FarthestInsertion)
+        );
+        assert!("nearest_neightbor".parse::<Heuristic>().is_err());
+    }
+}
@@ -0,0 +1,573 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use super::{
+    cluster::Clusters, cost::Cost, matrix::DistanceMatrix, precedence::Precedences,
+    time_window::TimeWindow, tour::TourKind, validation::MIN_DIMENSION,
+};
+
+/// A problem read from a file, distances kept as `f64` until the cost type
+/// is chosen with `matrix`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    pub name: String,
+    pub size: usize,
+    /// Row-major distances
+    pub weights: Vec<f64>,
+    /// A path from the first to the last city for sequential ordering problems
+    pub kind: TourKind,
+    /// `(first, then)` pairs of a sequential ordering problem
+    pub precedences: Vec<(usize, usize)>,
+    /// Cities of every cluster of a generalized TSP, one of each is visited
+    pub clusters: Vec<Vec<usize>>,
+}
+
+#[derive(Debug)]
+pub enum InstanceError {
+    Io(std::io::Error),
+    Format(String),
+}
+
+impl fmt::Display for InstanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstanceError::Io(error) => write!(f, "Could not read the instance: {}", error),
+            InstanceError::Format(message) => write!(f, "Malformed instance: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for InstanceError {}
+
+impl From<std::io::Error> for InstanceError {
+    fn from(error: std::io::Error) -> Self {
+        InstanceError::Io(error)
+    }
+}
+
+fn format_error<T>(message: impl Into<String>) -> Result<T, InstanceError> {
+    Err(InstanceError::Format(message.into()))
+}
+
+fn parse_number(token: &str) -> Result<f64, InstanceError> {
+    token
+        .parse()
+        .or_else(|_| format_error(format!("expected a number, got {:?}", token)))
+}
+
+/// Node coordinates and the TSPLIB rule turning them into distances
+struct Coordinates {
+    kind: String,
+    points: Vec<(f64, f64)>,
+}
+
+impl Coordinates {
+    fn distance(&self, from: usize, to: usize) -> f64 {
+        let ((x1, y1), (x2, y2)) = (self.points[from], self.points[to]);
+        let (dx, dy) = (x1 - x2, y1 - y2);
+        match self.kind.as_str() {
+            "EUC_2D" => (dx * dx + dy * dy).sqrt().round(),
+            "CEIL_2D" => (dx * dx + dy * dy).sqrt().ceil(),
+            "ATT" => {
+                let r = ((dx * dx + dy * dy) / 10.0).sqrt();
+                if r.round() < r {
+                    r.round() + 1.0
+                } else {
+                    r.round()
+                }
+            }
+            _ => unreachable!("Unsupported coordinate types are rejected while parsing"),
+        }
+    }
+}
+
+impl Instance {
+    /// Reads a `.csv` matrix or, for any other extension, a TSPLIB file
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, InstanceError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut instance = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Instance::from_csv(&name, &text)?,
+            _ => Instance::from_tsplib(&text)?,
+        };
+        if instance.name.is_empty() {
+            instance.name = name;
+        }
+        Ok(instance)
+    }
+
+    /// One row of the matrix per line, entries separated by commas
+    pub fn from_csv(name: &str, text: &str) -> Result<Self, InstanceError> {
+        let rows = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                line.split(',')
+                    .map(|token| parse_number(token.trim()))
+                    .collect()
+            })
+            .collect::<Result<Vec<Vec<f64>>, _>>()?;
+        let size = rows.len();
+        if let Some((i, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != size) {
+            return format_error(format!(
+                "expected {} columns, row {} has {}",
+                size,
+                i,
+                row.len()
+            ));
+        }
+        Ok(Instance {
+            name: name.to_string(),
+            size,
+            weights: rows.into_iter().flatten().collect(),
+            kind: TourKind::Cycle,
+            precedences: vec![],
+            clusters: vec![],
+        })
+    }
+
+    /// TSPLIB `TSP` and `ATSP` files with explicit weights
+    /// (full matrix or one of its triangles) or 2D coordinates, `SOP`
+    /// files, whose -1 weights say that the column city comes first, and
+    /// `GTSP` and `AGTSP` files with their sets of cities
+    pub fn from_tsplib(text: &str) -> Result<Self, InstanceError> {
+        let mut name = String::new();
+        let mut sequential = false;
+        let mut size = None;
+        let mut weight_type = String::from("EXPLICIT");
+        let mut weight_format = String::from("FULL_MATRIX");
+        let mut lines = text.lines();
+        let mut weights = None;
+        let mut coordinates = None;
+        let mut sets = None;
+        let mut clusters = vec![];
+
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (line, ""),
+            };
+            match key {
+                "NAME" => name = value.to_string(),
+                "TYPE" => sequential = value == "SOP",
+                "DIMENSION" => {
+                    let dimension = value.parse::<usize>().or_else(|_| {
+                        format_error(format!("DIMENSION is not a number: {:?}", value))
+                    })?;
+                    if dimension < MIN_DIMENSION {
+                        return format_error(format!(
+                            "DIMENSION {} is below the {} cities of the smallest instance",
+                            dimension, MIN_DIMENSION
+                        ));
+                    }
+                    size = Some(dimension);
+                }
+                "EDGE_WEIGHT_TYPE" => weight_type = value.to_string(),
+                "EDGE_WEIGHT_FORMAT" => weight_format = value.to_string(),
+                "EDGE_WEIGHT_SECTION" => {
+                    let size = size.ok_or_else(|| {
+                        InstanceError::Format("DIMENSION has to precede the weights".to_string())
+                    })?;
+                    let count = match weight_format.as_str() {
+                        "FULL_MATRIX" => size * size,
+                        "UPPER_ROW" | "LOWER_ROW" => size * (size - 1) / 2,
+                        "UPPER_DIAG_ROW" | "LOWER_DIAG_ROW" => size * (size + 1) / 2,
+                        other => return format_error(format!("unsupported format {}", other)),
+                    };
+                    // SOP weight sections repeat the dimension first
+                    let skipped = sequential as usize;
+                    let mut numbers = read_numbers(&mut lines, count + skipped)?;
+                    if sequential && numbers[0] != size as f64 {
+                        return format_error(format!(
+                            "the SOP weights start with {}, not the dimension",
+                            numbers[0]
+                        ));
+                    }
+                    numbers.drain(..skipped);
+                    weights = Some(numbers);
+                }
+                "NODE_COORD_SECTION" => {
+                    let size = size.ok_or_else(|| {
+                        InstanceError::Format("DIMENSION has to precede the nodes".to_string())
+                    })?;
+                    let numbers = read_numbers(&mut lines, 3 * size)?;
+                    coordinates = Some(numbers.chunks(3).map(|node| (node[1], node[2])).collect());
+                }
+                "GTSP_SETS" => {
+                    sets = Some(value.parse::<usize>().or_else(|_| {
+                        format_error(format!("GTSP_SETS is not a number: {:?}", value))
+                    })?)
+                }
+                "GTSP_SET_SECTION" => {
+                    let (Some(size), Some(sets)) = (size, sets) else {
+                        return format_error("DIMENSION and GTSP_SETS have to precede the sets");
+                    };
+                    clusters = read_sets(&mut lines, sets, size)?;
+                }
+                "EOF" => break,
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or_else(|| InstanceError::Format("missing DIMENSION".to_string()))?;
+        let weights = match (weight_type.as_str(), weights, coordinates) {
+            ("EXPLICIT", Some(weights), _) => expand(&weight_format, size, &weights),
+            ("EUC_2D" | "CEIL_2D" | "ATT", _, Some(points)) => {
+                let coordinates = Coordinates {
+                    kind: weight_type,
+                    points,
+                };
+                (0..size * size)
+                    .map(|i| coordinates.distance(i / size, i % size))
+                    .collect()
+            }
+            ("EXPLICIT", None, _) => return format_error("missing EDGE_WEIGHT_SECTION"),
+            (other, _, _) => return format_error(format!("unsupported weight type {}", other)),
+        };
+        let mut instance = Instance {
+            name,
+            size,
+            weights,
+            kind: TourKind::Cycle,
+            precedences: vec![],
+            clusters,
+        };
+        if sequential {
+            instance.read_precedences();
+        }
+        Ok(instance)
+    }
+
+    /// Turns the -1 weights into precedences; the arcs they stood for can
+    /// never be travelled and get a weight above any tour
+    fn read_precedences(&mut self) {
+        let n = self.size;
+        let forbidden = (0..n)
+            .map(|from| {
+                (0..n)
+                    .map(|to| self.weights[from * n + to])
+                    .fold(0.0, f64::max)
+            })
+            .sum::<f64>()
+            + 1.0;
+        for from in 0..n {
+            for to in 0..n {
+                let weight = &mut self.weights[from * n + to];
+                if from != to && *weight == -1.0 {
+                    self.precedences.push((to, from));
+                    *weight = forbidden;
+                }
+            }
+        }
+        self.kind = TourKind::Path {
+            start: Some(0),
+            end: Some(n - 1),
+        };
+    }
+
+    /// True when every distance is a whole number, so integer costs are exact
+    pub fn is_integral(&self) -> bool {
+        self.weights
+            .iter()
+            .all(|weight| weight.fract() == 0.0 && weight.abs() < i64::MAX as f64)
+    }
+
+    pub fn matrix<C: Cost>(&self) -> DistanceMatrix<C> {
+        let data = self
+            .weights
+            .iter()
+            .map(|&weight| C::from_f64(weight))
+            .collect();
+        let mut dmatrix = DistanceMatrix::from_flat(data, self.size).with_kind(self.kind);
+        if !self.precedences.is_empty() {
+            dmatrix = dmatrix.with_precedences(Precedences::new(self.size, &self.precedences));
+        }
+        if !self.clusters.is_empty() {
+            dmatrix = dmatrix.with_clusters(Clusters::new(self.clusters.clone()));
+        }
+        dmatrix
+    }
+}
+
+/// An instance with the cost of its best known tour
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownInstance {
+    pub instance: Instance,
+    pub optimum: f64,
+}
+
+impl KnownInstance {
+    /// Deficit of a cost over the optimum, in percent of the optimum
+    pub fn deficit(&self, cost: f64) -> f64 {
+        (cost - self.optimum) / self.optimum * 100.0
+    }
+}
+
+/// Best known costs by instance name, one `name,cost` line each;
+/// a header line is skipped
+pub fn read_optima(path: impl AsRef<Path>) -> Result<HashMap<String, f64>, InstanceError> {
+    parse_optima(&fs::read_to_string(path)?)
+}
+
+pub fn parse_optima(text: &str) -> Result<HashMap<String, f64>, InstanceError> {
+    let mut optima = HashMap::new();
+    let lines = text.lines().filter(|line| !line.trim().is_empty());
+    for (i, line) in lines.enumerate() {
+        let (name, cost) = match line.split_once(',') {
+            Some((name, cost)) => (name.trim(), cost.trim()),
+            None => return format_error(format!("expected name,cost, got {:?}", line)),
+        };
+        match parse_number(cost) {
+            Ok(cost) => {
+                optima.insert(name.to_string(), cost);
+            }
+            Err(_) if i == 0 => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(optima)
+}
+
+/// Time windows of the cities in order, one `ready,due,service` line each;
+/// a header line is skipped
+pub fn read_time_windows(path: impl AsRef<Path>) -> Result<Vec<TimeWindow>, InstanceError> {
+    parse_time_windows(&fs::read_to_string(path)?)
+}
+
+pub fn parse_time_windows(text: &str) -> Result<Vec<TimeWindow>, InstanceError> {
+    let mut windows = vec![];
+    let lines = text.lines().filter(|line| !line.trim().is_empty());
+    for (i, line) in lines.enumerate() {
+        let numbers = line
+            .split(',')
+            .map(|token| parse_number(token.trim()))
+            .collect::<Result<Vec<f64>, _>>();
+        match numbers {
+            Ok(numbers) if numbers.len() == 3 => windows.push(TimeWindow {
+                ready: numbers[0],
+                due: numbers[1],
+                service: numbers[2],
+            }),
+            Err(_) if i == 0 => {}
+            _ => return format_error(format!("expected ready,due,service, got {:?}", line)),
+        }
+    }
+    Ok(windows)
+}
+
+pub fn read_demands(path: impl AsRef<Path>) -> Result<Vec<f64>, InstanceError> {
+    parse_demands(&fs::read_to_string(path)?)
+}
+
+/// One demand per line in city order, after an optional header
+pub fn parse_demands(text: &str) -> Result<Vec<f64>, InstanceError> {
+    let mut demands = vec![];
+    let lines = text.lines().filter(|line| !line.trim().is_empty());
+    for (i, line) in lines.enumerate() {
+        match parse_number(line.trim()) {
+            Ok(demand) => demands.push(demand),
+            Err(_) if i == 0 => {}
+            Err(_) => return format_error(format!("expected a demand, got {:?}", line)),
+        }
+    }
+    Ok(demands)
+}
+
+fn read_numbers<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    count: usize,
+) -> Result<Vec<f64>, InstanceError> {
+    let mut numbers = Vec::with_capacity(count);
+    while numbers.len() < count {
+        let line = match lines.next() {
+            Some(line) => line,
+            None => return format_error(format!("expected {} numbers", count)),
+        };
+        for token in line.split_whitespace() {
+            numbers.push(parse_number(token)?);
+        }
+    }
+    numbers.truncate(count);
+    Ok(numbers)
+}
+
+/// The sets of a GTSP file, each its 1-based number and nodes ended by -1,
+/// as 0-based cities
+fn read_sets<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    count: usize,
+    size: usize,
+) -> Result<Vec<Vec<usize>>, InstanceError> {
+    let mut sets = vec![vec![]; count];
+    let mut current = None;
+    let mut read = 0;
+    while read < count {
+        let line = match lines.next() {
+            Some(line) => line,
+            None => return format_error(format!("expected {} sets", count)),
+        };
+        for token in line.split_whitespace() {
+            let number = parse_number(token)?;
+            let index = |limit: usize, what: &str| {
+                if number.fract() == 0.0 && number >= 1.0 && number <= limit as f64 {
+                    Ok(number as usize - 1)
+                } else {
+                    format_error(format!("{} {} is out of 1 to {}", what, token, limit))
+                }
+            };
+            match current {
+                None => current = Some(index(count, "set")?),
+                Some(_) if number == -1.0 => {
+                    current = None;
+                    read += 1;
+                }
+                Some(set) => sets[set].push(index(size, "node")?),
+            }
+        }
+    }
+    Ok(sets)
+}
+
+/// Full row-major matrix from a TSPLIB explicit weight section
+fn expand(format: &str, size: usize, weights: &[f64]) -> Vec<f64> {
+    if format == "FULL_MATRIX" {
+        return weights.to_vec();
+    }
+    let mut matrix = vec![0.0; size * size];
+    let mut values = weights.iter();
+    for row in 0..size {
+        let columns = match format {
+            "UPPER_ROW" => row + 1..size,
+            "UPPER_DIAG_ROW" => row..size,
+            "LOWER_ROW" => 0..row,
+            _ => 0..row + 1,
+        };
+        for column in columns {
+            let value = *values.next().unwrap();
+            matrix[row * size + column] = value;
+            matrix[column * size + row] = value;
+        }
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_atsp() {
+        let text = "NAME: tiny\nTYPE: ATSP\nDIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                    EDGE_WEIGHT_FORMAT: FULL_MATRIX\nEDGE_WEIGHT_SECTION\n\
+                    9999 1 2\n3 9999\n4 5 6 9999\nEOF\n";
+        let instance = Instance::from_tsplib(text).unwrap();
+        assert_eq!(instance.name, "tiny");
+        assert!(instance.is_integral());
+        let dmatrix = instance.matrix::<i64>();
+        assert_eq!(dmatrix.cost(1, 0), 3);
+        assert_eq!(dmatrix.cost(2, 1), 6);
+    }
+
+    #[test]
+    fn test_sequential_ordering() {
+        let text = "NAME: tiny.sop\nTYPE: SOP\nDIMENSION: 4\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                    EDGE_WEIGHT_FORMAT: FULL_MATRIX\nEDGE_WEIGHT_SECTION\n4\n\
+                    0 1 2 100\n-1 0 3 4\n-1 -1 0 5\n-1 -1 -1 0\nEOF\n";
+        let instance = Instance::from_tsplib(text).unwrap();
+        assert_eq!(instance.precedences.len(), 6);
+        assert!(instance.precedences.contains(&(1, 2)));
+        assert!(instance.weights.iter().all(|&weight| weight >= 0.0));
+        let dmatrix = instance.matrix::<i64>();
+        assert_eq!(dmatrix.kind, TourKind::new(false, Some(0), Some(3)));
+        dmatrix.validate().unwrap();
+        assert!(dmatrix.precedences.unwrap().holds(&[0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn test_triangle_and_coordinates() {
+        let text = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\nEDGE_WEIGHT_FORMAT: UPPER_ROW\n\
+                    EDGE_WEIGHT_SECTION\n1 2\n3\nEOF\n";
+        let instance = Instance::from_tsplib(text).unwrap();
+        assert_eq!(
+            instance.weights,
+            vec![0.0, 1.0, 2.0, 1.0, 0.0, 3.0, 2.0, 3.0, 0.0]
+        );
+
+        let text = "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EUC_2D\nNODE_COORD_SECTION\n\
+                    1 0 0\n2 3 4\n3 0 4\nEOF\n";
+        let instance = Instance::from_tsplib(text).unwrap();
+        assert_eq!(
+            instance.weights,
+            vec![0.0, 5.0, 4.0, 5.0, 0.0, 3.0, 4.0, 3.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_optima() {
+        let optima = parse_optima("name,optimum\nbr17, 39\nftv33,1286\n").unwrap();
+        assert_eq!(optima["br17"], 39.0);
+        assert_eq!(optima.len(), 2);
+        assert!(parse_optima("br17,39\nftv33,x\n").is_err());
+    }
+
+    #[test]
+    fn test_time_windows() {
+        let windows = parse_time_windows("ready,due,service\n0,100,0\n5, inf, 2.5\n").unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[1].due, f64::INFINITY);
+        assert_eq!(windows[1].service, 2.5);
+        assert!(parse_time_windows("0,100\n").is_err());
+    }
+
+    #[test]
+    fn test_generalized() {
+        let text = "NAME: 2tiny\nTYPE: AGTSP\nDIMENSION: 4\nGTSP_SETS: 2\n\
+                    EDGE_WEIGHT_TYPE: EXPLICIT\nEDGE_WEIGHT_FORMAT: FULL_MATRIX\n\
+                    EDGE_WEIGHT_SECTION\n0 1 2 3\n4 0 5 6\n7 8 0 9\n1 2 3 0\n\
+                    GTSP_SET_SECTION:\n1 1 3 -1\n2 2\n4 -1\nEOF\n";
+        let instance = Instance::from_tsplib(text).unwrap();
+        assert_eq!(instance.clusters, vec![vec![0, 2], vec![1, 3]]);
+        let dmatrix = instance.matrix::<i64>();
+        assert_eq!(dmatrix.tour_size(), 2);
+        assert_eq!(dmatrix.clusters.unwrap().cluster_of(3), 1);
+        let missing = text.replace("2 2\n4 -1\n", "");
+        assert!(Instance::from_tsplib(&missing).is_err());
+        let outside = text.replace("2 2\n4", "2 2\n5");
+        assert!(Instance::from_tsplib(&outside).is_err());
+    }
+
+    #[test]
+    fn test_demands() {
+        assert_eq!(
+            parse_demands("demand\n0\n2.5\n\n3\n").unwrap(),
+            vec![0.0, 2.5, 3.0]
+        );
+        assert!(parse_demands("0\n1,2\n").is_err());
+    }
+
+    #[test]
+    fn test_csv() {
+        let instance = Instance::from_csv("m", "0, 1.5\n2, 0\n").unwrap();
+        assert!(!instance.is_integral());
+        assert!(Instance::from_csv("m", "0, 1\n2\n").is_err());
+    }
+
+    #[test]
+    fn test_tsplib_rejects_small_dimensions() {
+        for dimension in [0, 1, 2] {
+            let text = format!(
+                "TYPE: ATSP\nDIMENSION: {}\nEDGE_WEIGHT_FORMAT: UPPER_ROW\nEDGE_WEIGHT_SECTION\nEOF\n",
+                dimension
+            );
+            assert!(matches!(
+                Instance::from_tsplib(&text),
+                Err(InstanceError::Format(_))
+            ));
+        }
+        assert!(Instance::from_tsplib("DIMENSION: -3\n").is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+
+use super::{
+    cluster::Clusters,
+    cost::Cost,
+    matrix::DistanceMatrix,
+    neighbours::{NeighbourLists, NeighbourRanking},
+    solution::Solution,
+};
+
+/// Longest segment moved by Or-opt, length 1 is plain node insertion
+const OR_OPT_MAX_LENGTH: usize = 3;
+
+/// Deterministic first-improvement local search over the matrix neighbour
+/// lists with don't-look bits. Only reversal-free moves are used, as reversing
+/// a part of an asymmetric tour changes the cost of every arc inside it.
+pub struct LocalSearch<'a, C: Cost> {
+    dmatrix: &'a DistanceMatrix<C>,
+    neighbours: &'a NeighbourLists,
+}
+
+/// Tour with O(1) successor, predecessor and position queries
+struct Tour {
+    path: Vec<usize>,
+    position: Vec<usize>,
+}
+
+impl Tour {
+    fn new(path: Vec<usize>) -> Self {
+        let mut position = vec![0; path.len()];
+        for (index, &city) in path.iter().enumerate() {
+            position[city] = index;
+        }
+        Tour { path, position }
+    }
+
+    fn size(&self) -> usize {
+        self.path.len()
+    }
+
+    #[inline]
+    fn next(&self, city: usize) -> usize {
+        self.path[(self.position[city] + 1) % self.size()]
+    }
+
+    #[inline]
+    fn prev(&self, city: usize) -> usize {
+        self.path[(self.position[city] + self.size() - 1) % self.size()]
+    }
+
+    /// Number of steps needed to get from `from` to `to`
+    #[inline]
+    fn offset(&self, from: usize, to: usize) -> usize {
+        (self.position[to] + self.size() - self.position[from]) % self.size()
+    }
+
+    /// Cities from `from` to `to` (both inclusive) in tour order
+    fn walk(&self, from: usize, to: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = self.position[from];
+        let steps = self.offset(from, to) + 1;
+        (0..steps).map(move |step| self.path[(start + step) % self.size()])
+    }
+
+    fn rebuild(&mut self, path: Vec<usize>) {
+        for (index, &city) in path.iter().enumerate() {
+            self.position[city] = index;
+        }
+        self.path = path;
+    }
+}
+
+impl<'a, C: Cost> LocalSearch<'a, C> {
+    pub fn new(dmatrix: &'a DistanceMatrix<C>) -> Self {
+        let neighbours = dmatrix
+            .neighbours
+            .as_ref()
+            .expect("Neighbour lists have to be computed before the local search");
+        LocalSearch {
+            dmatrix,
+            neighbours,
+        }
+    }
+
+    /// Improves the solution in place until no move applies,
+    /// returns by how much the cost went down
+    pub fn polish(&self, solution: &mut Solution) -> C {
+        if let Some(clusters) = &self.dmatrix.clusters {
+            return self.polish_clusters(clusters, solution);
+        }
+        let n = solution.size;
+        // Smaller tours have no room for non-trivial reversal-free moves
+        if n < 5 {
+            return C::ZERO;
+        }
+        let before = solution.cost(self.dmatrix);
+        let mut tour = Tour::new(std::mem::take(&mut solution.path));
+        let mut queue: VecDeque<usize> = tour.path.iter().copied().collect();
+        let mut queued = vec![true; n];
+
+        while let Some(city) = queue.pop_front() {
+            queued[city] = false;
+            let touched = self
+                .improve_or_opt(&mut tour, city)
+                .or_else(|| self.improve_segment_exchange(&mut tour, city));
+            if let Some(touched) = touched {
+                for other in touched.into_iter().chain(std::iter::once(city)) {
+                    if !queued[other] {
+                        queued[other] = true;
+                        queue.push_back(other);
+                    }
+                }
+            }
+        }
+
+        solution.path = tour.path;
+        before - solution.cost(self.dmatrix)
+    }
+
+    /// Alternates the search on the cluster order of a generalized TSP, its
+    /// cities kept, with choosing the cities anew for the order, until
+    /// neither improves the tour
+    fn polish_clusters(&self, clusters: &Clusters, solution: &mut Solution) -> C {
+        let m = solution.size;
+        let before = solution.cost(self.dmatrix);
+        let k = usize::min(self.neighbours.successors[0].len(), m - 1);
+        loop {
+            let mut chosen = vec![0; m];
+            for &city in solution.path.iter() {
+                chosen[clusters.cluster_of(city)] = city;
+            }
+            let data = chosen
+                .iter()
+                .flat_map(|&from| chosen.iter().map(move |&to| self.dmatrix.cost(from, to)))
+                .collect();
+            let mut fixed = DistanceMatrix::from_flat(data, m);
+            fixed.compute_neighbours(k, NeighbourRanking::Cost);
+            let mut order = clusters.order(solution);
+            let reordered = LocalSearch::new(&fixed).polish(&mut order);
+            solution.path = order.path.iter().map(|&cluster| chosen[cluster]).collect();
+            let reselected = clusters.reselect(self.dmatrix, solution);
+            if reordered + reselected <= C::EPSILON {
+                break;
+            }
+        }
+        before - solution.cost(self.dmatrix)
+    }
+
+    /// Moves the segment starting at `first` (1 to 3 cities long) between
+    /// two other adjacent cities. Returns the endpoints of the changed arcs.
+    fn improve_or_opt(&self, tour: &mut Tour, first: usize) -> Option<Vec<usize>> {
+        let dmatrix = self.dmatrix;
+        let n = tour.size();
+        let mut last = first;
+        for length in 1..=OR_OPT_MAX_LENGTH {
+            if length > 1 {
+                last = tour.next(last);
+            }
+            if length + 2 > n {
+                break;
+            }
+            let before = tour.prev(first);
+            let after = tour.next(last);
+            let removal_gain = dmatrix.cost(before, first) + dmatrix.cost(last, after)
+                - dmatrix.cost(before, after);
+            if removal_gain <= C::EPSILON {
+                continue;
+            }
+
+            let in_segment = |city: usize| tour.offset(first, city) < length;
+            let by_predecessor = self.neighbours.predecessors[first]
+                .iter()
+                .map(|&tail| (tail, tour.next(tail)));
+            let by_successor = self.neighbours.successors[last]
+                .iter()
+                .map(|&head| (tour.prev(head), head));
+            for (tail, head) in by_predecessor.chain(by_successor) {
+                if in_segment(tail) || in_segment(head) || tail == before {
+                    continue;
+                }
+                let insertion_cost =
+                    dmatrix.cost(tail, first) + dmatrix.cost(last, head) - dmatrix.cost(tail, head);
+                if insertion_cost < removal_gain - C::EPSILON {
+                    let segment: Vec<usize> = tour.walk(first, last).collect();
+                    let mut path = Vec::with_capacity(n);
+                    for city in tour.walk(after, before) {
+                        path.push(city);
+                        if city == tail {
+                            path.extend(segment.iter());
+                        }
+                    }
+                    tour.rebuild(path);
+                    return Some(vec![before, after, tail, head, last]);
+                }
+            }
+        }
+        None
+    }
+
+    /// Reversal-free 3-opt: for arcs a->a', b->b' and c->c' in tour order
+    /// swaps the segments [a', b] and [b', c], giving a->b', c->a' and b->c'
+    fn improve_segment_exchange(&self, tour: &mut Tour, a: usize) -> Option<Vec<usize>> {
+        let dmatrix = self.dmatrix;
+        let n = tour.size();
+        let a_next = tour.next(a);
+        for &b_next in self.neighbours.successors[a].iter() {
+            let first_gain = dmatrix.cost(a, a_next) - dmatrix.cost(a, b_next);
+            if first_gain <= C::EPSILON {
+                break;
+            }
+            let b_offset = tour.offset(a_next, b_next);
+            if b_offset < 1 || b_offset > n - 2 {
+                continue;
+            }
+            let b = tour.prev(b_next);
+            for &c_next in self.neighbours.successors[b].iter() {
+                let second_gain = first_gain + dmatrix.cost(b, b_next) - dmatrix.cost(b, c_next);
+                if second_gain <= C::EPSILON {
+                    break;
+                }
+                let c = tour.prev(c_next);
+                let c_offset = tour.offset(a_next, c);
+                if c_offset < b_offset || c_offset > n - 2 {
+                    continue;
+                }
+                let gain = second_gain + dmatrix.cost(c, c_next) - dmatrix.cost(c, a_next);
+                if gain > C::EPSILON {
+                    let mut path: Vec<usize> = tour.walk(b_next, c).collect();
+                    path.extend(tour.walk(a_next, b));
+                    path.extend(tour.walk(c_next, a));
+                    tour.rebuild(path);
+                    return Some(vec![a_next, b, b_next, c, c_next]);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::helpers::CountUnique;
+
+    use crate::utils::neighbours::NeighbourRanking;
+
+    use super::*;
+
+    fn asymmetric_matrix(size: usize) -> DistanceMatrix {
+        DistanceMatrix::modular(size, [31, 17, 7, 23])
+    }
+
+    #[test]
+    fn test_polish_reports_improvement() {
+        let mut dmatrix = asymmetric_matrix(30);
+        dmatrix.compute_neighbours(8, NeighbourRanking::Cost);
+        let local_search = LocalSearch::new(&dmatrix);
+        let mut solution = Solution::new((0..30).collect());
+        let before = solution.cost(&dmatrix);
+        let improvement = local_search.polish(&mut solution);
+        assert!(improvement > 0.0);
+        assert_eq!(before - improvement, solution.cost(&dmatrix));
+        assert_eq!(solution.path.iter().unique(), 30);
+    }
+
+    #[test]
+    fn test_polish_moves_misplaced_city() {
+        // The optimal cycle is 0 -> 1 -> ... -> 5 -> 0, city 3 is misplaced
+        let matrix = (0..6)
+            .map(|i| {
+                (0..6)
+                    .map(|j| if j == (i + 1) % 6 { 1.0 } else { 10.0 })
+                    .collect()
+            })
+            .collect();
+        let mut dmatrix = DistanceMatrix::new(matrix);
+        dmatrix.compute_neighbours(5, NeighbourRanking::Cost);
+        let local_search = LocalSearch::new(&dmatrix);
+        let mut solution = Solution::new(vec![0, 3, 1, 2, 4, 5]);
+        local_search.polish(&mut solution);
+        assert_eq!(solution.cost(&dmatrix), 6.0);
+    }
+}
@@ -1,17 +1,103 @@
+use super::{
+    cluster::Clusters, cost::Cost, fleet::Fleet, neighbours::NeighbourLists,
+    precedence::Precedences, time_window::TimeWindows, tour::TourKind,
+};
+
+/// Square distance matrix stored as one row-major buffer
 #[derive(Debug, Clone)]
-pub struct DistanceMatrix {
-    pub matrix: Vec<Vec<f64>>,
+pub struct DistanceMatrix<C: Cost = f64> {
+    pub data: Vec<C>,
     pub size: usize,
+    pub neighbours: Option<NeighbourLists>,
+    /// Whether tours close into a cycle, a cycle unless set with `with_kind`
+    pub kind: TourKind,
+    /// Order constraints of a sequential ordering problem, only on paths
+    pub precedences: Option<Precedences>,
+    /// When each city can be served, for an ATSPTW
+    pub time_windows: Option<TimeWindows>,
+    /// Vehicles sharing the cities, for routing from a depot
+    pub fleet: Option<Fleet>,
+    /// Groups of cities of which a tour visits one each, for a generalized TSP
+    pub clusters: Option<Clusters>,
 }
 
-impl DistanceMatrix {
-    pub fn new(matrix: Vec<Vec<f64>>) -> Self {
+impl<C: Cost> DistanceMatrix<C> {
+    pub fn new(matrix: Vec<Vec<C>>) -> Self {
         let size = matrix.len();
         for row in matrix.iter() {
             assert_eq!(row.len(), size)
         }
 
-        DistanceMatrix { matrix, size }
+        DistanceMatrix::from_flat(matrix.into_iter().flatten().collect(), size)
+    }
+
+    pub fn from_flat(data: Vec<C>, size: usize) -> Self {
+        assert_eq!(data.len(), size * size);
+        DistanceMatrix {
+            data,
+            size,
+            neighbours: None,
+            kind: TourKind::Cycle,
+            precedences: None,
+            time_windows: None,
+            fleet: None,
+            clusters: None,
+        }
+    }
+
+    pub fn with_kind(self, kind: TourKind) -> Self {
+        DistanceMatrix { kind, ..self }
+    }
+
+    pub fn with_precedences(self, precedences: Precedences) -> Self {
+        DistanceMatrix {
+            precedences: Some(precedences),
+            ..self
+        }
+    }
+
+    pub fn with_time_windows(self, time_windows: TimeWindows) -> Self {
+        DistanceMatrix {
+            time_windows: Some(time_windows),
+            ..self
+        }
+    }
+
+    pub fn with_fleet(self, fleet: Fleet) -> Self {
+        DistanceMatrix {
+            fleet: Some(fleet),
+            ..self
+        }
+    }
+
+    pub fn with_clusters(self, clusters: Clusters) -> Self {
+        DistanceMatrix {
+            clusters: Some(clusters),
+            ..self
+        }
+    }
+
+    /// Cities of a tour: one per cluster of a generalized TSP, all otherwise
+    #[inline]
+    pub fn tour_size(&self) -> usize {
+        self.clusters
+            .as_ref()
+            .map_or(self.size, |clusters| clusters.count())
+    }
+
+    #[inline]
+    pub fn cost(&self, from: usize, to: usize) -> C {
+        self.data[from * self.size + to]
+    }
+
+    #[inline]
+    pub fn row(&self, from: usize) -> &[C] {
+        &self.data[from * self.size..(from + 1) * self.size]
+    }
+
+    /// Every entry as `f64`, row-major, for the algorithms working on floats
+    pub fn to_f64(&self) -> Vec<f64> {
+        self.data.iter().map(|cost| cost.to_f64()).collect()
     }
 }
 
@@ -19,6 +105,30 @@ impl DistanceMatrix {
 fn test_from_matrix_vec() {
     let vector: Vec<Vec<f64>> = vec![vec![0.0, 2.0], vec![3.0, 0.0]];
     let dmatrix: DistanceMatrix = DistanceMatrix::new(vector);
-    let expected = vec![vec![0.0, 2.0], vec![3.0, 0.0]];
-    assert_eq!(dmatrix.matrix, expected);
+    let expected = vec![0.0, 2.0, 3.0, 0.0];
+    assert_eq!(dmatrix.data, expected);
+    assert_eq!(dmatrix.cost(1, 0), 3.0);
+    assert_eq!(dmatrix.row(0), [0.0, 2.0]);
+}
+
+#[test]
+fn test_integer_matrix() {
+    let dmatrix: DistanceMatrix<i32> = DistanceMatrix::new(vec![vec![0, 7], vec![9, 0]]);
+    assert_eq!(dmatrix.cost(0, 1), 7);
+    assert_eq!(dmatrix.to_f64(), vec![0.0, 7.0, 9.0, 0.0]);
+}
+
+#[cfg(test)]
+impl<C: Cost> DistanceMatrix<C> {
+    /// Asymmetric test instance costing `(i * a + j * b + i * j * c) % p + 1`
+    /// from `i` to `j`, for the coefficients `[a, b, c, p]`
+    pub(crate) fn modular(size: usize, [a, b, c, p]: [usize; 4]) -> Self {
+        let data = (0..size * size)
+            .map(|at| {
+                let (i, j) = (at / size, at % size);
+                C::from_f64(((i * a + j * b + i * j * c) % p + 1) as f64)
+            })
+            .collect();
+        DistanceMatrix::from_flat(data, size)
+    }
 }
@@ -1,6 +1,31 @@
+#[cfg(feature = "python")]
+pub mod array;
+pub mod assignment;
+pub mod auto;
+pub mod batch;
+pub mod benchmark;
+pub mod cluster;
+pub mod config;
+pub mod cost;
+pub mod crossover;
+pub mod exact;
+pub mod fleet;
 pub mod helpers;
+pub mod heuristics;
+pub mod instance;
+pub mod local_search;
 pub mod matrix;
+pub mod moves;
+pub mod neighbours;
 pub mod params;
+pub mod precedence;
+pub mod random;
 pub mod solution;
+pub mod solver;
 pub mod state;
+pub mod tabu;
 pub mod temp;
+pub mod time_window;
+pub mod tour;
+pub mod tuner;
+pub mod validation;
@@ -0,0 +1,152 @@
+use super::{cost::Cost, matrix::DistanceMatrix, solution::Solution};
+
+/// A metropolis proposal, applied and undone in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    /// `Solution::swap_parts` with the same arguments, its own inverse
+    Swap {
+        first: usize,
+        second: usize,
+        length: usize,
+    },
+    /// `Solution::shuffle` of the window, undone from a backup of it
+    Shuffle { start: usize, length: usize },
+    /// Puts `city` at the position in place of another city of its cluster,
+    /// for generalized TSPs
+    Choose { position: usize, city: usize },
+}
+
+impl Move {
+    /// Sum of the arcs the move can change, taken on the current path.
+    /// Called before and after `apply`, the difference is the cost delta.
+    pub fn arcs_cost<C: Cost>(&self, solution: &Solution, dmatrix: &DistanceMatrix<C>) -> C {
+        let mut total = C::ZERO;
+        self.for_each_arc(solution, |from, to| total = total + dmatrix.cost(from, to));
+        total
+    }
+
+    /// The arcs the move can change, taken on the current path
+    pub fn arcs(&self, solution: &Solution, arcs: &mut Vec<(usize, usize)>) {
+        arcs.clear();
+        self.for_each_arc(solution, |from, to| arcs.push((from, to)));
+    }
+
+    fn for_each_arc(&self, solution: &Solution, mut visit: impl FnMut(usize, usize)) {
+        let n = solution.size;
+        let mut arc = |position: usize| {
+            visit(
+                solution.path[position % n],
+                solution.path[(position + 1) % n],
+            )
+        };
+        match *self {
+            Move::Swap {
+                first,
+                second,
+                length,
+            } => {
+                if length == 0 {
+                    return;
+                }
+                let (low, high) = (usize::min(first, second), usize::max(first, second));
+                let (a, b) = solution.find_swap_indices(low, high, length);
+                // Arcs entering and leaving both segments, adjacent segments share one
+                let positions =
+                    [a + n - 1, a + length - 1, b + n - 1, b + length - 1].map(|p| p % n);
+                for (i, &position) in positions.iter().enumerate() {
+                    if !positions[..i].contains(&position) {
+                        arc(position);
+                    }
+                }
+            }
+            Move::Shuffle { start, length } => {
+                if length + 1 >= n {
+                    (0..n).for_each(arc);
+                } else {
+                    (0..=length).for_each(|k| arc(start + n - 1 + k));
+                }
+            }
+            Move::Choose { position, .. } => {
+                arc(position + n - 1);
+                arc(position);
+            }
+        }
+    }
+
+    /// Applies the move, saving what `undo` needs into `backup`
+    pub fn apply(&self, solution: &mut Solution, backup: &mut Vec<usize>) {
+        match *self {
+            Move::Swap {
+                first,
+                second,
+                length,
+            } => solution.swap_parts(first, second, length),
+            Move::Shuffle { start, length } => {
+                let n = solution.size;
+                backup.clear();
+                backup.extend((0..length).map(|k| solution.path[(start + k) % n]));
+                solution.shuffle(start, length);
+            }
+            Move::Choose { position, city } => {
+                backup.clear();
+                backup.push(solution.path[position]);
+                solution.path[position] = city;
+            }
+        }
+    }
+
+    pub fn undo(&self, solution: &mut Solution, backup: &[usize]) {
+        match *self {
+            Move::Swap {
+                first,
+                second,
+                length,
+            } => solution.swap_parts(first, second, length),
+            Move::Shuffle { start, .. } => {
+                let n = solution.size;
+                for (k, &city) in backup.iter().enumerate() {
+                    solution.path[(start + k) % n] = city;
+                }
+            }
+            Move::Choose { position, .. } => solution.path[position] = backup[0],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn asymmetric_matrix(size: usize) -> DistanceMatrix<i64> {
+        DistanceMatrix::modular(size, [31, 17, 7, 23])
+    }
+
+    proptest! {
+        #[test]
+        fn prop_delta_matches_cost_and_undo_restores(
+            (n, shuffle, a, b, length) in (3usize..40).prop_flat_map(|n| {
+                (Just(n), any::<bool>(), 0..n, 0..n, 0..=n / 2)
+            })
+        ) {
+            let dmatrix = asymmetric_matrix(n);
+            let mut solution = Solution::new((0..n).collect());
+            let proposal = if shuffle {
+                Move::Shuffle { start: a, length: 2 * length }
+            } else {
+                Move::Swap { first: a, second: b, length }
+            };
+            let before = solution.cost(&dmatrix);
+            let mut backup = Vec::new();
+
+            let removed = proposal.arcs_cost(&solution, &dmatrix);
+            proposal.apply(&mut solution, &mut backup);
+            let added = proposal.arcs_cost(&solution, &dmatrix);
+            prop_assert_eq!(before - removed + added, solution.cost(&dmatrix));
+
+            proposal.undo(&mut solution, &backup);
+            prop_assert_eq!(solution.path, (0..n).collect::<Vec<usize>>());
+        }
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(feature = "python")]
+use pyo3::{exceptions::PyValueError, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::{assignment::solve_assignment, cost::Cost, matrix::DistanceMatrix};
+
+/// How candidate neighbours are ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NeighbourRanking {
+    /// Plain arc cost
+    Cost,
+    /// Alpha-nearness on the assignment relaxation: the reduced cost of the arc
+    /// under optimal assignment duals, i.e. by how much the bound grows if the
+    /// arc is forced in. Arcs of the optimal assignment rank first.
+    #[serde(rename = "alpha")]
+    AlphaNearness,
+}
+
+impl FromStr for NeighbourRanking {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "cost" => Ok(NeighbourRanking::Cost),
+            "alpha" => Ok(NeighbourRanking::AlphaNearness),
+            _ => Err(format!("Unknown neighbour ranking: {}", name)),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl<'source> FromPyObject<'source> for NeighbourRanking {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        let name: String = ob.extract()?;
+        name.parse().map_err(PyValueError::new_err)
+    }
+}
+
+/// Precomputed k most promising successors and predecessors of every city
+#[derive(Debug, Clone)]
+pub struct NeighbourLists {
+    pub successors: Vec<Vec<usize>>,
+    pub predecessors: Vec<Vec<usize>>,
+}
+
+impl NeighbourLists {
+    pub fn new<C: Cost>(dmatrix: &DistanceMatrix<C>, k: usize, ranking: NeighbourRanking) -> Self {
+        let n = dmatrix.size;
+        let k = usize::min(k, n.saturating_sub(1));
+        let assignment = match ranking {
+            NeighbourRanking::AlphaNearness if n >= 2 => Some(solve_assignment(dmatrix)),
+            _ => None,
+        };
+        let rank = |from: usize, to: usize| {
+            let cost = dmatrix.cost(from, to).to_f64();
+            match &assignment {
+                Some(assignment) => assignment.reduced_cost(cost, from, to),
+                None => cost,
+            }
+        };
+
+        // Ties are broken by the plain cost, so alpha lists stay sensible
+        let closest = |city: usize, arc: &dyn Fn(usize) -> (usize, usize)| -> Vec<usize> {
+            let compare = |&a: &usize, &b: &usize| {
+                let ((a_from, a_to), (b_from, b_to)) = (arc(a), arc(b));
+                rank(a_from, a_to).total_cmp(&rank(b_from, b_to)).then(
+                    dmatrix
+                        .cost(a_from, a_to)
+                        .total_cmp(&dmatrix.cost(b_from, b_to)),
+                )
+            };
+            let mut others: Vec<usize> = (0..n).filter(|&other| other != city).collect();
+            if k < others.len() {
+                others.select_nth_unstable_by(k, compare);
+                others.truncate(k);
+            }
+            others.sort_by(compare);
+            others
+        };
+        let successors = (0..n)
+            .map(|city| closest(city, &|other| (city, other)))
+            .collect();
+        let predecessors = (0..n)
+            .map(|city| closest(city, &|other| (other, city)))
+            .collect();
+        NeighbourLists {
+            successors,
+            predecessors,
+        }
+    }
+}
+
+impl<C: Cost> DistanceMatrix<C> {
+    /// Computes and stores the candidate lists used by the heuristics
+    /// and the local search
+    pub fn compute_neighbours(&mut self, k: usize, ranking: NeighbourRanking) {
+        self.neighbours = Some(NeighbourLists::new(self, k, ranking));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_ranking() {
+        let mut dmatrix = DistanceMatrix::new(vec![
+            vec![0.0, 3.0, 1.0, 2.0],
+            vec![1.0, 0.0, 5.0, 9.0],
+            vec![4.0, 2.0, 0.0, 7.0],
+            vec![8.0, 6.0, 3.0, 0.0],
+        ]);
+        dmatrix.compute_neighbours(2, NeighbourRanking::Cost);
+        let neighbours = dmatrix.neighbours.unwrap();
+        assert_eq!(neighbours.successors[0], vec![2, 3]);
+        assert_eq!(neighbours.predecessors[0], vec![1, 2]);
+        assert_eq!(neighbours.successors[3].len(), 2);
+    }
+
+    #[test]
+    fn test_alpha_ranking_puts_assignment_first() {
+        let mut dmatrix = DistanceMatrix::new(vec![
+            vec![0.0, 5.0, 1.0],
+            vec![1.0, 0.0, 5.0],
+            vec![5.0, 1.0, 0.0],
+        ]);
+        dmatrix.compute_neighbours(1, NeighbourRanking::AlphaNearness);
+        let neighbours = dmatrix.neighbours.unwrap();
+        assert_eq!(neighbours.successors, vec![vec![2], vec![0], vec![1]]);
+    }
+}
@@ -1,6 +1,14 @@
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-#[derive(FromPyObject)]
+use serde::{Deserialize, Serialize};
+
+use super::{crossover::Crossover, heuristics::Heuristic, neighbours::NeighbourRanking};
+
+/// Settings of the annealing, see `ptsa_rust/parameters.py` for their meaning
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[cfg_attr(feature = "python", derive(FromPyObject))]
 pub struct Params {
     pub number_of_states: usize,
     pub number_of_repeats: usize,
@@ -14,4 +22,59 @@ pub struct Params {
     pub swap_states_probability: f64,
     pub closeness: f64,
     pub cooling_rate: f64,
+    pub heuristics: Vec<Heuristic>,
+    pub heuristic_keep_fraction: f64,
+    pub exact_solver_max_size: usize,
+    pub number_of_neighbours: usize,
+    pub neighbour_ranking: NeighbourRanking,
+    pub polish_final: bool,
+    pub local_search_period: usize,
+    pub auto: bool,
+    pub crossover_period: usize,
+    pub crossovers: Vec<Crossover>,
+    pub tabu_tenure: usize,
+    pub penalty_weight: f64,
+    pub penalty_adaptation: f64,
+    pub probability_of_choice: f64,
+}
+
+/// Same defaults as the Python `Parameters` dataclass
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            number_of_states: 40,
+            number_of_repeats: 10,
+            min_temperature: 0.1,
+            max_temperature: 50.0,
+            probability_of_shuffle: 0.3,
+            probability_of_heuristic: 0.5,
+            temp_beta_a: 1.0,
+            temp_beta_b: 1.0,
+            max_length_percent_of_cycle: 0.3,
+            swap_states_probability: 0.2,
+            closeness: 1.5,
+            cooling_rate: 0.95,
+            heuristics: vec![
+                Heuristic::NearestNeighbor,
+                Heuristic::BackwardNearestNeighbor,
+            ],
+            heuristic_keep_fraction: 0.1,
+            exact_solver_max_size: 20,
+            number_of_neighbours: 8,
+            neighbour_ranking: NeighbourRanking::Cost,
+            polish_final: true,
+            local_search_period: 0,
+            auto: false,
+            crossover_period: 0,
+            crossovers: vec![
+                Crossover::Order,
+                Crossover::EdgeRecombination,
+                Crossover::EdgeAssembly,
+            ],
+            tabu_tenure: 0,
+            penalty_weight: 1.0,
+            penalty_adaptation: 0.05,
+            probability_of_choice: 0.3,
+        }
+    }
 }
@@ -0,0 +1,288 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use super::{moves::Move, solution::Solution, validation::ValidationError};
+
+/// Order constraints of a sequential ordering problem: every city comes
+/// after all of its predecessors along the path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Precedences {
+    /// Cities that have to be visited before each city
+    pub before: Vec<Vec<usize>>,
+}
+
+impl Precedences {
+    /// From `(first, then)` pairs over `size` cities
+    pub fn new(size: usize, pairs: &[(usize, usize)]) -> Self {
+        let mut before = vec![vec![]; size];
+        for &(first, then) in pairs {
+            before[then].push(first);
+        }
+        Precedences { before }
+    }
+
+    pub fn size(&self) -> usize {
+        self.before.len()
+    }
+
+    /// Pairs out of range, on the same city or closing a cycle of
+    /// constraints make the instance infeasible
+    pub fn validate(&self, size: usize) -> Result<(), ValidationError> {
+        let invalid = |message: String| Err(ValidationError::Matrix(message));
+        if self.size() != size {
+            return invalid(format!(
+                "precedences are given for {} cities, the matrix has {}",
+                self.size(),
+                size
+            ));
+        }
+        for (then, before) in self.before.iter().enumerate() {
+            if let Some(&first) = before.iter().find(|&&first| first >= size || first == then) {
+                return invalid(format!("city {} cannot precede city {}", first, then));
+            }
+        }
+        if self
+            .topological_order(&(0..size).collect::<Vec<usize>>())
+            .len()
+            < size
+        {
+            return invalid("the precedences contain a cycle".to_string());
+        }
+        Ok(())
+    }
+
+    /// True when the cities of the path are in an order the constraints allow
+    pub fn holds(&self, path: &[usize]) -> bool {
+        let mut position = vec![0; self.size()];
+        for (i, &city) in path.iter().enumerate() {
+            position[city] = i;
+        }
+        path.iter().all(|&city| {
+            self.before[city]
+                .iter()
+                .all(|&first| position[first] < position[city])
+        })
+    }
+
+    /// Stable topological sort: the cities in the order of the path as far as
+    /// the constraints allow, each time the earliest one whose predecessors
+    /// are all placed
+    pub fn repaired(&self, path: &[usize]) -> Vec<usize> {
+        let order = self.topological_order(path);
+        assert_eq!(order.len(), path.len(), "Precedences have to be acyclic");
+        order
+    }
+
+    fn topological_order(&self, path: &[usize]) -> Vec<usize> {
+        let n = self.size();
+        let mut position = vec![0; n];
+        for (i, &city) in path.iter().enumerate() {
+            position[city] = i;
+        }
+        let mut after = vec![vec![]; n];
+        let mut waiting = vec![0; n];
+        for (then, before) in self.before.iter().enumerate() {
+            waiting[then] = before.len();
+            for &first in before {
+                after[first].push(then);
+            }
+        }
+        let mut ready: BinaryHeap<Reverse<(usize, usize)>> = path
+            .iter()
+            .filter(|&&city| waiting[city] == 0)
+            .map(|&city| Reverse((position[city], city)))
+            .collect();
+        let mut order = Vec::with_capacity(n);
+        while let Some(Reverse((_, city))) = ready.pop() {
+            order.push(city);
+            for &then in after[city].iter() {
+                waiting[then] -= 1;
+                if waiting[then] == 0 {
+                    ready.push(Reverse((position[then], then)));
+                }
+            }
+        }
+        order
+    }
+}
+
+/// Feasibility of the tours of a closed path instance, whose order starts
+/// after the `origin` city, with marks reused between the checks
+#[derive(Debug, Clone)]
+pub struct PrecedenceCheck {
+    pub origin: usize,
+    marks: Vec<u32>,
+    stamp: u32,
+}
+
+impl PrecedenceCheck {
+    pub fn new(size: usize, origin: usize) -> Self {
+        PrecedenceCheck {
+            origin,
+            marks: vec![0; size],
+            stamp: 0,
+        }
+    }
+
+    /// Cities of the tour in order, starting after the origin
+    pub fn order(&self, solution: &Solution) -> Vec<usize> {
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == self.origin)
+            .unwrap();
+        solution.path[at + 1..]
+            .iter()
+            .chain(solution.path[..at].iter())
+            .copied()
+            .collect()
+    }
+
+    pub fn holds(&self, precedences: &Precedences, solution: &Solution) -> bool {
+        precedences.holds(&self.order(solution))
+    }
+
+    /// Puts the cities of the tour in a feasible order, keeping the origin
+    /// in place
+    pub fn repair(&self, precedences: &Precedences, solution: &mut Solution) {
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == self.origin)
+            .unwrap();
+        let order = precedences.repaired(&self.order(solution));
+        let n = solution.size;
+        for (k, city) in order.into_iter().enumerate() {
+            solution.path[(at + 1 + k) % n] = city;
+        }
+    }
+
+    /// Whether a feasible tour is still feasible after the move was applied.
+    /// Only the cities between the two ends of the move change their order,
+    /// so only their constraints are checked unless the origin moved.
+    pub fn allows(
+        &mut self,
+        precedences: &Precedences,
+        proposal: &Move,
+        solution: &Solution,
+    ) -> bool {
+        let n = solution.size;
+        let windows = match *proposal {
+            Move::Swap {
+                first,
+                second,
+                length,
+            } => {
+                if length == 0 {
+                    return true;
+                }
+                // The segments and either of the arcs between them
+                let (low, high) = (usize::min(first, second), usize::max(first, second));
+                let (a, b) = solution.find_swap_indices(low, high, length);
+                let forward = (b + n - a) % n + length;
+                let backward = (a + n - b) % n + length;
+                [Some((a, forward)), Some((b, backward))]
+            }
+            Move::Shuffle { start, length } => [Some((start, usize::min(length, n))), None],
+            // Another city takes the place, the whole order is checked
+            Move::Choose { .. } => [None, None],
+        };
+        for (start, length) in windows.into_iter().flatten() {
+            if let Some(holds) = self.window_holds(precedences, solution, start, length) {
+                return holds;
+            }
+        }
+        self.holds(precedences, solution)
+    }
+
+    /// Checks the constraints among the cities of a window, `None` when the
+    /// origin is inside it and the order around it may have changed
+    fn window_holds(
+        &mut self,
+        precedences: &Precedences,
+        solution: &Solution,
+        start: usize,
+        length: usize,
+    ) -> Option<bool> {
+        let n = solution.size;
+        if self.stamp >= u32::MAX - 2 {
+            self.marks.iter_mut().for_each(|mark| *mark = 0);
+            self.stamp = 0;
+        }
+        self.stamp += 2;
+        let (inside, placed) = (self.stamp, self.stamp + 1);
+        for k in 0..length {
+            let city = solution.path[(start + k) % n];
+            if city == self.origin {
+                return None;
+            }
+            self.marks[city] = inside;
+        }
+        for k in 0..length {
+            let city = solution.path[(start + k) % n];
+            // A predecessor still ahead in the window comes too late
+            if precedences.before[city]
+                .iter()
+                .any(|&first| self.marks[first] == inside)
+            {
+                return Some(false);
+            }
+            self.marks[city] = placed;
+        }
+        Some(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_repair_is_a_stable_topological_sort() {
+        let precedences = Precedences::new(5, &[(3, 1), (4, 0)]);
+        assert_eq!(precedences.repaired(&[0, 1, 2, 3, 4]), vec![2, 3, 1, 4, 0]);
+        assert!(precedences.holds(&[2, 3, 1, 4, 0]));
+        assert!(!precedences.holds(&[0, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_rejects_cycles() {
+        let precedences = Precedences::new(4, &[(0, 1), (1, 2), (2, 0)]);
+        assert!(precedences.validate(4).is_err());
+        assert!(Precedences::new(4, &[(0, 1), (1, 2)]).validate(4).is_ok());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_window_check_matches_full_check(
+            pairs in prop::collection::vec((0usize..11, 0usize..11), 0..12),
+            shuffle in any::<bool>(),
+            first in 0usize..12,
+            second in 0usize..12,
+            length in 0usize..=6,
+        ) {
+            // Only pairs going forward in the city numbering, so acyclic;
+            // city 11 is the origin of the closed tour
+            let pairs: Vec<(usize, usize)> = pairs
+                .into_iter()
+                .filter(|(a, b)| a < b)
+                .collect();
+            let precedences = Precedences::new(12, &pairs);
+            let mut check = PrecedenceCheck::new(12, 11);
+            let mut solution = Solution::new((0..12).collect());
+            check.repair(&precedences, &mut solution);
+            prop_assert!(check.holds(&precedences, &solution));
+
+            let proposal = if shuffle {
+                Move::Shuffle { start: first, length: 2 * length }
+            } else {
+                Move::Swap { first, second, length }
+            };
+            let mut backup = vec![];
+            proposal.apply(&mut solution, &mut backup);
+            let full = check.holds(&precedences, &solution);
+            prop_assert_eq!(check.allows(&precedences, &proposal, &solution), full);
+        }
+    }
+}
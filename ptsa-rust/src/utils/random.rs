@@ -0,0 +1,57 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+thread_local! {
+    static GENERATOR: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// The random generator of the current thread, seeded by the system
+/// unless fixed with `reseed`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SolverRng;
+
+pub fn rng() -> SolverRng {
+    SolverRng
+}
+
+/// Makes the following draws of the current thread repeat for the same seed
+pub fn reseed(seed: u64) {
+    GENERATOR.with(|generator| *generator.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+impl RngCore for SolverRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        GENERATOR.with(|generator| generator.borrow_mut().next_u32())
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        GENERATOR.with(|generator| generator.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        GENERATOR.with(|generator| generator.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        GENERATOR.with(|generator| generator.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_reseed_repeats_draws() {
+        reseed(7);
+        let first: Vec<u32> = (0..5).map(|_| rng().gen()).collect();
+        reseed(7);
+        let second: Vec<u32> = (0..5).map(|_| rng().gen()).collect();
+        assert_eq!(first, second);
+    }
+}
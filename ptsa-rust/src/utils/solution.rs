@@ -1,18 +1,19 @@
-use rand::{rngs::ThreadRng, seq::SliceRandom, thread_rng, Rng};
+use rand::{seq::SliceRandom, Rng};
 
-use super::matrix::DistanceMatrix;
+use super::{cost::Cost, matrix::DistanceMatrix, random::rng};
 
 #[inline]
-fn gen_index(rng: &mut ThreadRng, lbound: usize, ubound: usize) -> usize {
-    if ubound <= (core::u32::MAX as usize) {
+fn gen_index(rng: &mut impl Rng, lbound: usize, ubound: usize) -> usize {
+    if ubound <= (u32::MAX as usize) {
         rng.gen_range(lbound as u32..ubound as u32) as usize
     } else {
         rng.gen_range(lbound..ubound)
     }
 }
 
+#[cfg(test)]
 fn shuffle_slice(slice: &mut [usize]) {
-    slice.shuffle(&mut thread_rng())
+    slice.shuffle(&mut rng())
 }
 
 #[derive(Debug, Clone)]
@@ -30,11 +31,14 @@ impl Solution {
 
     pub fn random_solution(size: usize) -> Self {
         let mut path: Vec<usize> = (0..size).collect();
-        path.shuffle(&mut thread_rng());
+        path.shuffle(&mut rng());
         Solution::new(path)
     }
 
-    pub fn nearest_neightbor_solution(dmatrix: &DistanceMatrix, starting_city: usize) -> Self {
+    pub fn nearest_neightbor_solution<C: Cost>(
+        dmatrix: &DistanceMatrix<C>,
+        starting_city: usize,
+    ) -> Self {
         if starting_city >= dmatrix.size {
             panic!("Impossible choice for the first city")
         }
@@ -47,13 +51,23 @@ impl Solution {
 
         let mut current_city = starting_city;
         for _ in 0..(dmatrix.size - 1) {
-            let next_city = dmatrix.matrix[current_city]
-                .iter()
-                .enumerate()
-                .filter(|(i, _)| *i != current_city && !(visited[*i]))
-                .min_by(|(_, a), (_, b)| a.total_cmp(b))
-                .map(|(i, _)| i)
-                .unwrap();
+            // The candidate list is tried first, the full row only when it is exhausted
+            let candidate = dmatrix.neighbours.as_ref().and_then(|neighbours| {
+                neighbours.successors[current_city]
+                    .iter()
+                    .find(|&&city| !visited[city])
+                    .copied()
+            });
+            let next_city = candidate.unwrap_or_else(|| {
+                dmatrix
+                    .row(current_city)
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != current_city && !(visited[*i]))
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(i, _)| i)
+                    .unwrap()
+            });
             path.push(next_city);
             visited[next_city] = true;
             current_city = next_city;
@@ -61,7 +75,10 @@ impl Solution {
         Solution::new(path)
     }
 
-    pub fn backwards_nearest_neightbor_solution(dmatrix: &DistanceMatrix, end_city: usize) -> Self {
+    pub fn backwards_nearest_neightbor_solution<C: Cost>(
+        dmatrix: &DistanceMatrix<C>,
+        end_city: usize,
+    ) -> Self {
         if end_city >= dmatrix.size {
             panic!("Impossible choice for the first city")
         }
@@ -73,16 +90,22 @@ impl Solution {
         visited[end_city] = true;
 
         let mut current_city = end_city;
-        for i in 0..(dmatrix.size - 1) {
-            let next_city = dmatrix
-                .matrix
-                .iter()
-                .map(|row| row[i])
-                .enumerate()
-                .filter(|(i, _)| *i != current_city && !(visited[*i]))
-                .min_by(|(_, a), (_, b)| a.total_cmp(b))
-                .map(|(i, _)| i)
-                .unwrap();
+        for _ in 0..(dmatrix.size - 1) {
+            let candidate = dmatrix.neighbours.as_ref().and_then(|neighbours| {
+                neighbours.predecessors[current_city]
+                    .iter()
+                    .find(|&&city| !visited[city])
+                    .copied()
+            });
+            let next_city = candidate.unwrap_or_else(|| {
+                (0..dmatrix.size)
+                    .map(|city| dmatrix.cost(city, current_city))
+                    .enumerate()
+                    .filter(|(i, _)| *i != current_city && !(visited[*i]))
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(i, _)| i)
+                    .unwrap()
+            });
             path.push(next_city);
             visited[next_city] = true;
             current_city = next_city;
@@ -93,18 +116,13 @@ impl Solution {
 }
 
 impl Solution {
+    /// Shuffles the `length` cities starting at `start`, wrapping around
+    /// the end of the path. Lengths above the size shuffle the whole cycle.
     pub fn shuffle(&mut self, start: usize, length: usize) {
         assert!(start < self.size);
-        if length > self.size {
-            eprintln!(
-                "Could not perform the shuffle operation with len {} decreasing to {}",
-                length, self.size
-            );
-            shuffle_slice(&mut self.path);
-            return;
-        }
+        let length = usize::min(length, self.size);
 
-        let rng = &mut thread_rng();
+        let rng = &mut rng();
         let max_length = self.path.len();
         for i in (start..(start + length)).rev() {
             let new_index = gen_index(rng, start, i + 1);
@@ -112,82 +130,98 @@ impl Solution {
         }
     }
 
-    fn find_swap_indices(
+    /// Start positions of the two disjoint segments swapped by `swap_parts`.
+    ///
+    /// The second segment starts at `second_index`. The first one is placed
+    /// inside the arc left free by the second, as close to `first_index` as
+    /// possible: starting at it if it fits, otherwise ending at it, otherwise
+    /// pushed to the end of the free arc. When `first_index` falls inside the
+    /// second segment the free arc is entered at its start.
+    pub fn find_swap_indices(
         &self,
         first_index: usize,
         second_index: usize,
         length: usize,
-    ) -> Option<(usize, usize)> {
-        assert!(first_index < self.size);
-        assert!(second_index < self.size);
-
-        let first_fit_between_second = first_index + length <= second_index;
-        if first_fit_between_second {
-            return Some((first_index, second_index));
-        }
-
-        let first_underflow = first_index < length - 1;
-        if !first_underflow {
-            return Some((first_index - length + 1, second_index));
-        }
-
-        let underflow_first: usize = first_index + self.path.len() + 1 - length;
-
-        let first_move_overlap = second_index + length > underflow_first;
-        if !first_move_overlap {
-            return Some((underflow_first, second_index));
-        }
-
-        None
+    ) -> (usize, usize) {
+        let n = self.size;
+        assert!(first_index < n);
+        assert!(second_index < n);
+        assert!(2 * length <= n, "Segments longer than half of the cycle");
+
+        // Free arc goes from just after the second segment to just before it
+        let free_start = (second_index + length) % n;
+        let free_length = n - length;
+        let offset = (first_index + n - free_start) % n;
+        let offset = if offset < free_length { offset } else { 0 };
+
+        let first_offset = if offset + length <= free_length {
+            offset
+        } else if offset + 1 >= length {
+            offset + 1 - length
+        } else {
+            free_length - length
+        };
+        ((free_start + first_offset) % n, second_index)
     }
 
+    /// Swaps two disjoint segments of `length` cities, keeping the order
+    /// inside each of them. Any length up to half of the cycle is valid,
+    /// segments can wrap around the end of the path.
     pub fn swap_parts(&mut self, mut first_index: usize, mut second_index: usize, length: usize) {
         assert!(first_index < self.size);
         assert!(second_index < self.size);
 
-        // Current implementation assumes that 3 * length - 2 <= N
-        // This assumption allows for the swap to always be deterministic and possible
-        if 3 * length - 2 > self.size {
-            panic!("Not possible swap")
-        }
-
         if first_index > second_index {
             std::mem::swap(&mut first_index, &mut second_index);
         }
 
         let max_len = self.path.len();
-        match self.find_swap_indices(first_index, second_index, length) {
-            Some((first, second)) => {
-                for offset in 0..length {
-                    let first_replace = (first + offset) % max_len;
-                    let second_replace = (second + offset) % max_len;
-                    self.path.swap(first_replace, second_replace);
-                }
-            }
-            None => panic!("Impossible indicies"),
+        let (first, second) = self.find_swap_indices(first_index, second_index, length);
+        for offset in 0..length {
+            let first_replace = (first + offset) % max_len;
+            let second_replace = (second + offset) % max_len;
+            self.path.swap(first_replace, second_replace);
         }
     }
 
-    pub fn cost(&self, dmatrix: &DistanceMatrix) -> f64 {
-        assert_eq!(dmatrix.size, self.size);
+    /// Length of the cycle, or of the path as listed for path instances
+    pub fn cost<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> C {
+        assert_eq!(dmatrix.tour_size(), self.size);
         // Calculate the length of that cycle using the distance matrix
-        let mut length = 0.0;
+        let mut length = C::ZERO;
         for i in 0..(self.size - 1) {
             let j = (i + 1) % self.size;
-            length += dmatrix.matrix[self.path[i]][self.path[j]];
+            length = length + dmatrix.cost(self.path[i], self.path[j]);
         }
-        length + dmatrix.matrix[self.path[self.size - 1]][self.path[0]]
+        if dmatrix.kind.is_path() {
+            return length;
+        }
+        length + dmatrix.cost(self.path[self.size - 1], self.path[0])
     }
 }
 
 #[derive(Clone, Debug)]
-pub struct ComputedSolution {
+pub struct ComputedSolution<C: Cost = f64> {
     pub solution: Solution,
-    pub cost: f64,
+    pub cost: C,
+}
+
+impl<C: Cost> ComputedSolution<C> {
+    /// Gap between the cost and a lower bound, in percent of the bound.
+    /// A zero bound gives no gap for a free tour and an infinite one otherwise.
+    pub fn gap(&self, lower_bound: f64) -> f64 {
+        let cost = self.cost.to_f64();
+        if lower_bound == 0.0 {
+            return if cost == 0.0 { 0.0 } else { f64::INFINITY };
+        }
+        (cost - lower_bound) / lower_bound * 100.0
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use crate::utils::helpers::CountUnique;
 
     use super::*;
@@ -255,6 +289,65 @@ mod tests {
         assert!(solution.path == vec![3, 4, 8, 0, 1, 5, 6, 7, 2]);
     }
 
+    #[test]
+    fn test_solution_swap_halves() {
+        let path = vec![0, 1, 2, 3, 4, 5];
+        let mut solution = Solution::new(path);
+        solution.swap_parts(1, 4, 3);
+        assert!(solution.path == vec![3, 4, 5, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_shuffle_longer_than_cycle() {
+        let mut solution = Solution::new((0..6).collect());
+        solution.shuffle(4, 10);
+        assert_eq!(solution.path.iter().unique(), 6);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_swap_exchanges_disjoint_segments(
+            (n, length, first, second) in (2usize..60).prop_flat_map(|n| {
+                (Just(n), 0..=n / 2, 0..n, 0..n)
+            })
+        ) {
+            let mut solution = Solution::new((0..n).collect());
+            let (low, high) = (usize::min(first, second), usize::max(first, second));
+            let (s1, s2) = solution.find_swap_indices(low, high, length);
+            solution.swap_parts(first, second, length);
+
+            let first_segment: Vec<usize> = (0..length).map(|k| (s1 + k) % n).collect();
+            let second_segment: Vec<usize> = (0..length).map(|k| (s2 + k) % n).collect();
+            prop_assert!(first_segment.iter().all(|i| !second_segment.contains(i)));
+            prop_assert_eq!(solution.path.iter().unique(), n);
+            for k in 0..length {
+                prop_assert_eq!(solution.path[first_segment[k]], second_segment[k]);
+                prop_assert_eq!(solution.path[second_segment[k]], first_segment[k]);
+            }
+            for i in 0..n {
+                if !first_segment.contains(&i) && !second_segment.contains(&i) {
+                    prop_assert_eq!(solution.path[i], i);
+                }
+            }
+        }
+
+        #[test]
+        fn prop_shuffle_stays_inside_window(
+            (n, start, length) in (1usize..40).prop_flat_map(|n| (Just(n), 0..n, 0..2 * n))
+        ) {
+            let mut solution = Solution::new((0..n).collect());
+            solution.shuffle(start, length);
+            prop_assert_eq!(solution.path.iter().unique(), n);
+            if length < n {
+                for i in 0..n {
+                    if (i + n - start) % n >= length {
+                        prop_assert_eq!(solution.path[i], i);
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_cost() {
         let matrix = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
@@ -264,6 +357,32 @@ mod tests {
         assert_eq!(solution.cost(&dmatrix), 2.0);
     }
 
+    #[test]
+    fn test_integer_cost_is_exact() {
+        let dmatrix = DistanceMatrix::new(vec![
+            vec![0, 100_000_001, 3],
+            vec![5, 0, 100_000_007],
+            vec![100_000_011, 2, 0],
+        ]);
+        let solution = Solution::new(vec![0, 1, 2]);
+        assert_eq!(solution.cost(&dmatrix), 300_000_019i64);
+    }
+
+    #[test]
+    fn test_gap() {
+        let computed = ComputedSolution {
+            solution: Solution::new(vec![0, 1]),
+            cost: 110.0,
+        };
+        assert_eq!(computed.gap(100.0), 10.0);
+        assert_eq!(computed.gap(0.0), f64::INFINITY);
+        let free = ComputedSolution {
+            solution: Solution::new(vec![0, 1]),
+            cost: 0.0,
+        };
+        assert_eq!(free.gap(0.0), 0.0);
+    }
+
     #[test]
     fn test_random_solution() {
         let random_sol: Solution = Solution::random_solution(10);
@@ -279,4 +398,17 @@ mod tests {
         assert_eq!(solution.path.iter().unique(), 2);
         assert_eq!(*(solution.path.first().unwrap()), 0);
     }
+
+    #[test]
+    fn test_backward_heuristic_follows_predecessors() {
+        // The cheap arcs into each city come from 1 -> 2 -> 3 -> 0; reading the
+        // column of the step instead of the current city went 2 -> 1 -> 3 -> 0
+        let mut matrix = vec![vec![10.0; 4]; 4];
+        matrix[1][2] = 1.0;
+        matrix[2][3] = 1.0;
+        matrix[3][0] = 1.0;
+        let dmatrix = DistanceMatrix::new(matrix);
+        let solution = Solution::backwards_nearest_neightbor_solution(&dmatrix, 0);
+        assert_eq!(solution.path, vec![1, 2, 3, 0]);
+    }
 }
@@ -0,0 +1,1007 @@
+use chrono::Utc;
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    sync::{Arc, Mutex},
+    thread::{self, ScopedJoinHandle},
+    time::{Duration, Instant},
+};
+
+use super::{
+    cost::Cost,
+    exact::{solve_exact, HELD_KARP_MAX_SIZE},
+    local_search::LocalSearch,
+    matrix::DistanceMatrix,
+    params::Params,
+    precedence::PrecedenceCheck,
+    random::{reseed, rng},
+    solution::{ComputedSolution, Solution},
+    state::{State, StatesContainer, TOLERANCE},
+    tabu::{TabuList, TabuStats},
+    temp::TemperatureBounds,
+    tour::TourKind,
+    validation::ValidationError,
+};
+
+/// When a run ends: as soon as any of the set criteria is met
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stop {
+    /// Wall-clock budget of the whole run
+    pub time: Option<Duration>,
+    /// Annealing iterations (one cooling step each) of every thread
+    pub iterations: Option<usize>,
+    /// Cost at or below which the search is over, e.g. a known optimum
+    pub target_cost: Option<f64>,
+}
+
+impl Stop {
+    pub fn after(time: Duration) -> Self {
+        Stop {
+            time: Some(time),
+            ..Stop::default()
+        }
+    }
+
+    pub fn after_iterations(iterations: usize) -> Self {
+        Stop {
+            iterations: Some(iterations),
+            ..Stop::default()
+        }
+    }
+
+    pub fn or_after_iterations(self, iterations: usize) -> Self {
+        Stop {
+            iterations: Some(iterations),
+            ..self
+        }
+    }
+
+    pub fn or_at_cost(self, target_cost: f64) -> Self {
+        Stop {
+            target_cost: Some(target_cost),
+            ..self
+        }
+    }
+
+    /// Only the target cost would never end a run on a hard instance
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.time.is_none() && self.iterations.is_none() {
+            return Err(ValidationError::Parameters(
+                "a time or iteration limit is needed to stop the search".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn reached(&self, started: Instant, iteration: usize, best_cost: f64) -> bool {
+        self.time.is_some_and(|time| started.elapsed() >= time)
+            || self.iterations.is_some_and(|limit| iteration > limit)
+            || self.target_cost.is_some_and(|target| best_cost <= target)
+    }
+}
+
+/// The best solution of a run with statistics gathered along the way
+#[derive(Clone, Debug)]
+pub struct RunSummary<C: Cost = f64> {
+    pub best: ComputedSolution<C>,
+    /// Assignment relaxation bound of the instance
+    pub lower_bound: f64,
+    /// Cost removed by the local search, periodic and final passes together
+    pub local_search_improvement: C,
+    pub elapsed: Duration,
+    /// When the search found the returned tour, before the final polish,
+    /// since the start of the run
+    pub time_to_best: Duration,
+    /// Parameters the search ran with, the derived ones in the automatic mode
+    pub params: Params,
+    /// Rulings of the tabu lists of every replica, zero without a tenure
+    pub tabu: TabuStats,
+    /// The tour meets the fixed ends, precedences and time windows of the
+    /// instance
+    pub feasible: bool,
+    /// How late the tour reaches every city, empty without time windows
+    pub lateness: Vec<f64>,
+    /// Cities of every vehicle's route in order, without the depot the
+    /// routes start and end at; empty without a fleet
+    pub routes: Vec<Vec<usize>>,
+    /// Total demand of every route, zero without demands
+    pub loads: Vec<f64>,
+}
+
+/// Parallel tempering simulated annealing solver
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use ptsa_rust::{DistanceMatrix, Params, Solver, Stop};
+///
+/// let dmatrix = DistanceMatrix::new(vec![vec![0, 3, 4], vec![2, 0, 5], vec![1, 6, 0]]);
+/// let summary = Solver::builder()
+///     .params(Params::default())
+///     .stop(Stop::after(Duration::from_secs(10)))
+///     .solve(&dmatrix)
+///     .unwrap();
+/// println!("{:?} costs {}", summary.best.solution.path, summary.best.cost);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Solver {
+    pub params: Params,
+    pub stop: Stop,
+    /// Print the progress to the standard output
+    pub verbose: bool,
+    /// Fixes the random draws, so runs limited by iterations repeat
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SolverBuilder {
+    params: Params,
+    stop: Stop,
+    verbose: bool,
+    seed: Option<u64>,
+}
+
+impl SolverBuilder {
+    pub fn params(self, params: Params) -> Self {
+        SolverBuilder { params, ..self }
+    }
+
+    pub fn stop(self, stop: Stop) -> Self {
+        SolverBuilder { stop, ..self }
+    }
+
+    pub fn verbose(self, verbose: bool) -> Self {
+        SolverBuilder { verbose, ..self }
+    }
+
+    pub fn seed(self, seed: u64) -> Self {
+        SolverBuilder {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> Result<Solver, ValidationError> {
+        self.params.validate()?;
+        self.stop.validate()?;
+        Ok(Solver {
+            params: self.params,
+            stop: self.stop,
+            verbose: self.verbose,
+            seed: self.seed,
+        })
+    }
+
+    pub fn solve<C: Cost>(
+        self,
+        dmatrix: &DistanceMatrix<C>,
+    ) -> Result<RunSummary<C>, ValidationError> {
+        self.build()?.solve(dmatrix)
+    }
+}
+
+/// An instance ready for the search, shared by any number of runs of
+/// solvers with the parameters it was prepared with
+#[derive(Debug, Clone)]
+pub struct Prepared<C: Cost = f64> {
+    /// The validated matrix with its candidate lists, closed into a cycle
+    /// instance for paths and expanded into a giant tour instance for fleets
+    pub dmatrix: DistanceMatrix<C>,
+    /// Kind of the tours of the original instance
+    pub kind: TourKind,
+    /// Best tours of every heuristic, the replicas start from them
+    pub heuristic_solutions: Vec<Solution>,
+    /// Assignment relaxation bound of the instance, of its cluster matrix for
+    /// a generalized TSP
+    pub lower_bound: f64,
+}
+
+impl<C: Cost> Prepared<C> {
+    /// Puts a tour of the closed instance in a feasible order: the fixed ends
+    /// next to the dummy city and every city after its predecessors
+    pub fn repair(&self, solution: &mut Solution) {
+        self.kind.repair(solution);
+        if let Some(precedences) = &self.dmatrix.precedences {
+            PrecedenceCheck::new(self.dmatrix.size, self.dmatrix.size - 1)
+                .repair(precedences, solution);
+        }
+    }
+
+    /// Whether a tour of the closed instance meets the constraints of the
+    /// original one
+    pub fn holds(&self, solution: &Solution) -> bool {
+        self.kind.holds(solution)
+            && self.dmatrix.precedences.as_ref().is_none_or(|precedences| {
+                PrecedenceCheck::new(self.dmatrix.size, self.dmatrix.size - 1)
+                    .holds(precedences, solution)
+            })
+    }
+
+    /// How late a tour of the closed instance reaches each city of the
+    /// original one, empty without time windows
+    pub fn lateness(&self, solution: &Solution) -> Vec<f64> {
+        let Some(windows) = &self.dmatrix.time_windows else {
+            return vec![];
+        };
+        let mut lateness = windows.lateness(&self.dmatrix, solution);
+        if self.kind.is_path() {
+            lateness.pop();
+        }
+        lateness
+    }
+
+    /// The routes of a giant tour of the expanded instance, empty without
+    /// a fleet
+    pub fn routes(&self, solution: &Solution) -> Vec<Vec<usize>> {
+        self.dmatrix
+            .fleet
+            .as_ref()
+            .map_or(vec![], |fleet| fleet.routes(solution))
+    }
+
+    /// Lateness and overload of a tour together
+    fn violation(&self, solution: &Solution) -> f64 {
+        let overload = self
+            .dmatrix
+            .fleet
+            .as_ref()
+            .map_or(0.0, |fleet| fleet.overload(&fleet.routes(solution)));
+        self.lateness(solution).iter().sum::<f64>() + overload
+    }
+
+    /// The tour of the original instance from one of the prepared instance
+    fn original_tour(&self, solution: &Solution) -> Solution {
+        match &self.dmatrix.fleet {
+            Some(fleet) => fleet.tour(&fleet.routes(solution)),
+            None => self.kind.opened(solution),
+        }
+    }
+}
+
+/// Best tour of a thread or of the whole run
+struct Outcome<C: Cost> {
+    best: ComputedSolution<C>,
+    local_search_improvement: C,
+    /// Since the start of the run
+    found: Duration,
+    tabu: TabuStats,
+    /// Lateness and overload of the best tour, zero without time windows
+    /// or capacities
+    violation: f64,
+}
+
+macro_rules! progress {
+    ($solver:expr, $($arg:tt)*) => {
+        if $solver.verbose {
+            println!($($arg)*);
+        }
+    };
+}
+
+impl Solver {
+    pub fn builder() -> SolverBuilder {
+        SolverBuilder::default()
+    }
+
+    /// Validates the instance and searches for the shortest tour
+    pub fn solve<C: Cost>(
+        &self,
+        dmatrix: &DistanceMatrix<C>,
+    ) -> Result<RunSummary<C>, ValidationError> {
+        let started = Instant::now();
+        if let Some(seed) = self.seed {
+            reseed(seed);
+        }
+        let prepared = self.prepare(dmatrix)?;
+        Ok(self.search(&prepared, started))
+    }
+
+    /// Validates the instance and computes what every run of it starts from:
+    /// the candidate lists, the heuristic tours and the lower bound
+    pub fn prepare<C: Cost>(
+        &self,
+        dmatrix: &DistanceMatrix<C>,
+    ) -> Result<Prepared<C>, ValidationError> {
+        dmatrix.validate()?;
+        let kind = dmatrix.kind;
+        let dmatrix = match &dmatrix.fleet {
+            Some(fleet) => fleet.expanded(dmatrix),
+            None => kind.closed(dmatrix),
+        };
+        // A generalized TSP is bounded and seeded on its cluster orders
+        let cluster_matrix = dmatrix
+            .clusters
+            .as_ref()
+            .map(|clusters| clusters.matrix(&dmatrix));
+        let lower_bound = cluster_matrix
+            .as_ref()
+            .unwrap_or(&dmatrix)
+            .assignment_lower_bound();
+        let mut prepared = Prepared {
+            dmatrix,
+            kind,
+            heuristic_solutions: vec![],
+            lower_bound,
+        };
+        if self.may_anneal(&prepared.dmatrix) {
+            prepared.dmatrix.compute_neighbours(
+                self.params.number_of_neighbours,
+                self.params.neighbour_ranking,
+            );
+            let mut solutions = match (&prepared.dmatrix.clusters, &cluster_matrix) {
+                (Some(clusters), Some(cluster_matrix)) => self
+                    .get_best_heuristic_solutions(cluster_matrix)
+                    .iter()
+                    .map(|order| clusters.choose(&prepared.dmatrix, order))
+                    .collect(),
+                _ => self.get_best_heuristic_solutions(&prepared.dmatrix),
+            };
+            solutions
+                .iter_mut()
+                .for_each(|solution| prepared.repair(solution));
+            // Heuristic giant tours are split into routes optimally
+            if let Some(fleet) = &prepared.dmatrix.fleet {
+                solutions
+                    .iter_mut()
+                    .for_each(|solution| *solution = fleet.resplit(&prepared.dmatrix, solution));
+            }
+            prepared.heuristic_solutions = solutions;
+        }
+        Ok(prepared)
+    }
+
+    /// Searches a prepared instance, the time limit starts now
+    pub fn solve_prepared<C: Cost>(&self, prepared: &Prepared<C>) -> RunSummary<C> {
+        let started = Instant::now();
+        if let Some(seed) = self.seed {
+            reseed(seed);
+        }
+        self.search(prepared, started)
+    }
+
+    fn search<C: Cost>(&self, prepared: &Prepared<C>, started: Instant) -> RunSummary<C> {
+        let dmatrix = &prepared.dmatrix;
+        if self.params.auto && self.may_anneal(dmatrix) {
+            let params = match &dmatrix.clusters {
+                // Tuned on the cluster orders, as long as the tours
+                Some(clusters) => self.params.auto_tuned(&clusters.matrix(dmatrix)),
+                None => self.params.auto_tuned(dmatrix),
+            };
+            progress!(
+                self,
+                "Automatic parameters: {} states, temperatures {:.3} to {:.3}, \
+                 segments up to {:.1}% of the tour, cooling rate {:.4}",
+                params.number_of_states,
+                params.min_temperature,
+                params.max_temperature,
+                100.0 * params.max_length_percent_of_cycle,
+                params.cooling_rate
+            );
+            let solver = Solver {
+                params,
+                ..self.clone()
+            };
+            return solver.summarize(prepared, started);
+        }
+        self.summarize(prepared, started)
+    }
+
+    fn summarize<C: Cost>(&self, prepared: &Prepared<C>, started: Instant) -> RunSummary<C> {
+        let outcome = self.run(prepared, started);
+        let solution = &outcome.best.solution;
+        let lateness = prepared.lateness(solution);
+        let routes = prepared.routes(solution);
+        let loads = prepared
+            .dmatrix
+            .fleet
+            .as_ref()
+            .map_or(vec![], |fleet| fleet.loads(&routes));
+        let feasible = prepared.holds(solution) && prepared.violation(solution) <= TOLERANCE;
+        let best = ComputedSolution {
+            solution: prepared.original_tour(solution),
+            cost: outcome.best.cost,
+        };
+        RunSummary {
+            best,
+            lower_bound: prepared.lower_bound,
+            local_search_improvement: outcome.local_search_improvement,
+            elapsed: started.elapsed(),
+            time_to_best: outcome.found,
+            params: self.params.clone(),
+            tabu: outcome.tabu,
+            feasible,
+            lateness,
+            routes,
+            loads,
+        }
+    }
+
+    /// Small instances go to the exact solvers, which know no precedences,
+    /// time windows, capacities or clusters. Branch and bound, above the
+    /// Held-Karp size, needs a time limit to give up by.
+    fn solves_exactly<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> bool {
+        dmatrix.size <= self.params.exact_solver_max_size
+            && (dmatrix.size <= HELD_KARP_MAX_SIZE || self.stop.time.is_some())
+            && dmatrix.precedences.is_none()
+            && dmatrix.time_windows.is_none()
+            && dmatrix.clusters.is_none()
+            && dmatrix
+                .fleet
+                .as_ref()
+                .is_none_or(|fleet| fleet.capacity.is_none())
+    }
+
+    /// Everything but Held-Karp may leave the instance to the annealer,
+    /// branch and bound when it runs out of time
+    fn may_anneal<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> bool {
+        !self.solves_exactly(dmatrix) || dmatrix.size > HELD_KARP_MAX_SIZE
+    }
+
+    #[inline]
+    fn get_best_heuristic_solutions<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> Vec<Solution> {
+        // Every heuristic keeps its own best fraction (at least one tour)
+        let mut heuristic_solutions: Vec<Solution> = vec![];
+        for heuristic in self.params.heuristics.iter() {
+            let mut solutions: Vec<(Solution, C)> = heuristic
+                .solutions(dmatrix)
+                .into_iter()
+                .map(|solution| {
+                    let cost = solution.cost(dmatrix);
+                    (solution, cost)
+                })
+                .collect();
+            solutions.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+            let takes = (solutions.len() as f64 * self.params.heuristic_keep_fraction) as usize;
+            heuristic_solutions.extend(
+                solutions
+                    .into_iter()
+                    .take(usize::max(takes, 1))
+                    .map(|(solution, _)| solution),
+            );
+        }
+        heuristic_solutions
+    }
+
+    #[inline]
+    fn init_states<'a, C: Cost>(
+        &self,
+        distance_matrix: &'a DistanceMatrix<C>,
+        starting_solutions: Vec<Solution>,
+    ) -> StatesContainer<'a, C> {
+        // Initialization
+        let problem_size = distance_matrix.tour_size();
+        for solution in starting_solutions.iter() {
+            assert_eq!(solution.size, problem_size);
+        }
+        let temp_bounds = TemperatureBounds {
+            max: self.params.max_temperature,
+            min: self.params.min_temperature,
+        };
+        // Creating states
+        let mut states = StatesContainer::new(temp_bounds.clone(), distance_matrix);
+        states.penalty_weight = self.params.penalty_weight;
+        states.probability_of_choice = self.params.probability_of_choice;
+        for solution in starting_solutions.into_iter() {
+            let (a, b) = (self.params.temp_beta_a, self.params.temp_beta_b);
+            let temperature = if self.params.auto {
+                temp_bounds.random_temperature_within(a, b)
+            } else {
+                temp_bounds.random_temperature(a, b)
+            };
+            let is_shuffle_transition = rng().gen::<f64>() < self.params.probability_of_shuffle;
+            let tenure = self.params.tabu_tenure;
+            let state = State {
+                solution,
+                is_shuffle_transition,
+                tabu: (tenure > 0).then(|| TabuList::new(tenure)),
+            };
+            states.add(state, temperature);
+        }
+        states
+    }
+
+    fn create_inital_states<'a, C: Cost>(
+        &self,
+        n: usize,
+        prepared: &'a Prepared<C>,
+    ) -> StatesContainer<'a, C> {
+        let distance_matrix = &prepared.dmatrix;
+        let heuristic_solutions = &prepared.heuristic_solutions;
+        let rng = &mut rng();
+        let solutions: Vec<Solution> = (0..n)
+            .map(|_| {
+                let take_heuristic = !heuristic_solutions.is_empty()
+                    && rng.gen_range(0.0..1.0) < self.params.probability_of_heuristic;
+                if take_heuristic {
+                    heuristic_solutions.choose(rng).unwrap().clone()
+                } else if let Some(clusters) = &distance_matrix.clusters {
+                    clusters.random_solution()
+                } else {
+                    let mut solution = Solution::random_solution(distance_matrix.size);
+                    prepared.repair(&mut solution);
+                    solution
+                }
+            })
+            .collect();
+        // TODO: Make it just an iterator. Do not collect into vector
+        self.init_states(distance_matrix, solutions)
+    }
+
+    fn run_thread<C: Cost>(
+        &self,
+        mut states: StatesContainer<C>,
+        started: Instant,
+        global_best: Arc<Mutex<f64>>,
+        thead_id: usize,
+        local_search: &LocalSearch<C>,
+    ) -> Outcome<C> {
+        let mut local_search_improvement = C::ZERO;
+        let mut found = started.elapsed();
+        let mut thread_best = (states.best_cost, states.best_violation);
+        // Main loop
+        for iteration in 1.. {
+            // Break condition
+            let global_best_cost = *global_best.lock().unwrap();
+            if self.stop.reached(started, iteration, global_best_cost) {
+                break;
+            }
+            // Metropolis and replica transitions
+            for _ in 0..self.params.number_of_repeats {
+                states.metropolis_tranision(self.params.max_length_percent_of_cycle);
+                for _ in 0..states.states.len() {
+                    states.replica_transition(
+                        self.params.swap_states_probability,
+                        self.params.closeness,
+                    )
+                }
+            }
+            // Cooling
+            states.cool(self.params.cooling_rate);
+            states.adapt_penalty(self.params.penalty_weight, self.params.penalty_adaptation);
+
+            // Polishing the incumbent
+            let period = self.params.local_search_period;
+            if period > 0 && iteration % period == 0 {
+                local_search_improvement =
+                    local_search_improvement + states.polish_best(local_search);
+            }
+
+            // Recombining the cheaper replicas
+            let period = self.params.crossover_period;
+            if period > 0 && iteration % period == 0 {
+                let crossover = *self.params.crossovers.choose(&mut rng()).unwrap();
+                states.recombine(crossover);
+            }
+
+            if (states.best_cost, states.best_violation) != thread_best {
+                thread_best = (states.best_cost, states.best_violation);
+                found = started.elapsed();
+            }
+
+            // Update global best, only feasible tours count for the target cost
+            if states.best_violation <= TOLERANCE {
+                let mut global_best_cost = global_best.lock().unwrap();
+                if states.best_cost.to_f64() < *global_best_cost {
+                    *global_best_cost = states.best_cost.to_f64();
+                    progress!(
+                        self,
+                        "{} -> {} -> {}",
+                        Utc::now(),
+                        thead_id,
+                        states.best_cost
+                    )
+                }
+            }
+        }
+        if self.verbose {
+            let ladder: Vec<String> = states
+                .ladder()
+                .iter()
+                .map(|(temperature, cost)| format!("{:.3}: {}", temperature, cost))
+                .collect();
+            println!("{} final ladder -> {}", thead_id, ladder.join(", "));
+        }
+        let tabu = states.tabu_stats();
+        let violation = states.best_violation;
+        let best = ComputedSolution {
+            solution: states.best_solution.unwrap(),
+            cost: states.best_cost,
+        };
+        Outcome {
+            best,
+            local_search_improvement,
+            found,
+            tabu,
+            violation,
+        }
+    }
+
+    fn run<C: Cost>(&self, prepared: &Prepared<C>, started: Instant) -> Outcome<C> {
+        let dmatrix = &prepared.dmatrix;
+        if self.solves_exactly(dmatrix) {
+            progress!(self, "Small instance, solving it exactly");
+            // Branch and bound gets half of the time limit, the annealer the rest
+            let deadline = self.stop.time.map(|time| started + time / 2);
+            if let Some(best) = solve_exact(dmatrix, deadline) {
+                return Outcome {
+                    best,
+                    local_search_improvement: C::ZERO,
+                    found: started.elapsed(),
+                    tabu: TabuStats::default(),
+                    violation: 0.0,
+                };
+            }
+            progress!(self, "The exact search ran out of time, annealing instead");
+        }
+
+        let local_search = LocalSearch::new(dmatrix);
+
+        // Just do one run of it
+        progress!(self, "Starting SEARCH part");
+        let n = self.params.number_of_repeats;
+
+        let global_best = Arc::new(Mutex::new(f64::INFINITY));
+        let mut results: Vec<Outcome<C>> = thread::scope(|s| {
+            let handlers: Vec<ScopedJoinHandle<'_, Outcome<C>>> = (0..n)
+                .map(|i| {
+                    progress!(self, "Starting thread number {}.", i);
+                    let initial_states =
+                        self.create_inital_states(self.params.number_of_states, prepared);
+                    let thead_global_best = Arc::clone(&global_best);
+                    let local_search = &local_search;
+                    // Drawn on this thread, so a fixed seed fixes every thread
+                    let thread_seed: u64 = rng().gen();
+                    s.spawn(move || {
+                        reseed(thread_seed);
+                        self.run_thread(initial_states, started, thead_global_best, i, local_search)
+                    })
+                })
+                .collect();
+            handlers
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        let mut local_search_improvement: C = results
+            .iter()
+            .map(|outcome| outcome.local_search_improvement)
+            .sum();
+        let tabu = results
+            .iter()
+            .fold(TabuStats::default(), |total, outcome| total + outcome.tabu);
+        results.sort_by(|a, b| {
+            a.violation
+                .total_cmp(&b.violation)
+                .then(a.best.cost.total_cmp(&b.best.cost))
+        });
+        let Outcome {
+            best: mut best_solution,
+            found,
+            mut violation,
+            ..
+        } = results.swap_remove(0);
+        progress!(self, "Finished searching for solutions.");
+        progress!(
+            self,
+            "Currently the best solution has cost of: {}",
+            best_solution.cost
+        );
+        if self.params.polish_final {
+            let before = best_solution.solution.clone();
+            let improvement = local_search.polish(&mut best_solution.solution);
+            let polished = &best_solution.solution;
+            if prepared.holds(polished)
+                && prepared.violation(polished) <= prepared.violation(&before) + TOLERANCE
+            {
+                best_solution.cost = best_solution.cost - improvement;
+                local_search_improvement = local_search_improvement + improvement;
+                progress!(self, "Local search improved it by {}", improvement);
+            } else {
+                best_solution.solution = before;
+                progress!(
+                    self,
+                    "Local search broke the precedences, time windows or capacities, dropped"
+                );
+            }
+        }
+        // The same cities split anew into routes
+        if let Some(fleet) = &dmatrix.fleet {
+            let split = fleet.resplit(dmatrix, &best_solution.solution);
+            let (split_cost, split_violation) = (split.cost(dmatrix), prepared.violation(&split));
+            let current = prepared.violation(&best_solution.solution);
+            if split_violation < current - TOLERANCE
+                || split_violation <= current + TOLERANCE && split_cost < best_solution.cost
+            {
+                progress!(self, "Splitting the routes anew: {}", split_cost);
+                best_solution = ComputedSolution {
+                    solution: split,
+                    cost: split_cost,
+                };
+                violation = split_violation;
+            }
+        }
+        Outcome {
+            best: best_solution,
+            local_search_improvement,
+            found,
+            tabu,
+            violation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{
+        cluster::Clusters,
+        fleet::Fleet,
+        precedence::Precedences,
+        time_window::{TimeWindow, TimeWindows},
+    };
+
+    #[test]
+    fn test_requires_a_limit() {
+        let result = Solver::builder()
+            .stop(Stop::default().or_at_cost(10.0))
+            .build();
+        assert!(matches!(result, Err(ValidationError::Parameters(_))));
+    }
+
+    #[test]
+    fn test_solves_without_python() {
+        let dmatrix = DistanceMatrix::<i64>::modular(30, [31, 17, 7, 23]);
+        let params = Params {
+            number_of_states: 8,
+            number_of_repeats: 2,
+            ..Params::default()
+        };
+        let summary = Solver::builder()
+            .params(params)
+            .stop(Stop::after_iterations(20))
+            .solve(&dmatrix)
+            .unwrap();
+        let mut path = summary.best.solution.path.clone();
+        assert_eq!(summary.best.cost, summary.best.solution.cost(&dmatrix));
+        assert!(summary.best.cost.to_f64() >= summary.lower_bound);
+        path.sort();
+        assert_eq!(path, (0..30).collect::<Vec<usize>>());
+        assert!(summary.time_to_best <= summary.elapsed);
+    }
+
+    #[test]
+    fn test_seed_repeats_the_run() {
+        let dmatrix = DistanceMatrix::<i64>::modular(25, [13, 29, 3, 31]);
+        let solver = Solver::builder()
+            .params(Params {
+                number_of_states: 6,
+                number_of_repeats: 3,
+                polish_final: false,
+                ..Params::default()
+            })
+            .stop(Stop::after_iterations(30))
+            .seed(11)
+            .build()
+            .unwrap();
+        let first = solver.solve(&dmatrix).unwrap();
+        let second = solver.solve(&dmatrix).unwrap();
+        assert_eq!(first.best.solution.path, second.best.solution.path);
+    }
+
+    #[test]
+    fn test_path_keeps_its_ends() {
+        let dmatrix = DistanceMatrix::<i64>::modular(28, [11, 19, 5, 37]);
+        let kind = TourKind::new(false, Some(5), Some(17));
+        let dmatrix = dmatrix.with_kind(kind);
+        let summary = Solver::builder()
+            .params(Params {
+                number_of_states: 8,
+                number_of_repeats: 2,
+                probability_of_heuristic: 0.0,
+                ..Params::default()
+            })
+            .stop(Stop::after_iterations(20))
+            .solve(&dmatrix)
+            .unwrap();
+        let path = &summary.best.solution.path;
+        assert_eq!(path.len(), 28);
+        assert_eq!((path[0], path[27]), (5, 17));
+        assert_eq!(summary.best.cost, summary.best.solution.cost(&dmatrix));
+    }
+
+    #[test]
+    fn test_sequential_ordering_stays_feasible() {
+        let size = 30;
+        let dmatrix = DistanceMatrix::<i64>::modular(size, [7, 23, 3, 41]);
+        // Chains of every third city, with the first city before and the last after all
+        let mut pairs: Vec<(usize, usize)> = (1..size - 4).map(|i| (i, i + 3)).collect();
+        pairs.extend((1..size - 1).map(|i| (0, i)));
+        pairs.extend((1..size - 1).map(|i| (i, size - 1)));
+        let precedences = Precedences::new(size, &pairs);
+        let dmatrix = dmatrix
+            .with_kind(TourKind::new(false, Some(0), Some(size - 1)))
+            .with_precedences(precedences.clone());
+        let summary = Solver::builder()
+            .params(Params {
+                number_of_states: 8,
+                number_of_repeats: 2,
+                crossover_period: 5,
+                local_search_period: 5,
+                ..Params::default()
+            })
+            .stop(Stop::after_iterations(30))
+            .solve(&dmatrix)
+            .unwrap();
+        assert!(summary.feasible);
+        assert!(precedences.holds(&summary.best.solution.path));
+        assert_eq!(summary.best.cost, summary.best.solution.cost(&dmatrix));
+    }
+
+    #[test]
+    fn test_time_windows_are_met() {
+        let size = 30;
+        let dmatrix = DistanceMatrix::<i64>::modular(size, [17, 11, 5, 43]);
+        // Windows around the arrivals of a scrambled tour from city 0
+        let tour: Vec<usize> = (1..size).map(|i| i * 7 % (size - 1) + 1).collect();
+        let mut windows = vec![
+            TimeWindow {
+                ready: 0.0,
+                due: 1e6,
+                service: 0.0
+            };
+            size
+        ];
+        let mut time = 0.0;
+        let mut from = 0;
+        for &city in tour.iter() {
+            time += dmatrix.cost(from, city) as f64 + 1.0;
+            windows[city] = TimeWindow {
+                ready: f64::max(time - 30.0, 0.0),
+                due: time + 30.0,
+                service: 1.0,
+            };
+            from = city;
+        }
+        let dmatrix = dmatrix.with_time_windows(TimeWindows::new(windows));
+        let summary = Solver::builder()
+            .params(Params {
+                number_of_states: 8,
+                number_of_repeats: 2,
+                local_search_period: 5,
+                ..Params::default()
+            })
+            .stop(Stop::after_iterations(3000))
+            .seed(5)
+            .solve(&dmatrix)
+            .unwrap();
+        assert_eq!(summary.lateness.len(), size);
+        assert!(summary.feasible, "lateness {:?}", summary.lateness);
+        assert_eq!(summary.best.cost, summary.best.solution.cost(&dmatrix));
+    }
+
+    #[test]
+    fn test_fleet_routes_stay_within_capacity() {
+        let size = 31;
+        let dmatrix = DistanceMatrix::<i64>::modular(size, [19, 13, 3, 37]);
+        let demands: Vec<f64> = (0..size).map(|city| (city % 4 + 1) as f64).collect();
+        // 75 units of demand for 4 vehicles carrying 24 each
+        let fleet = Fleet::new(4, 0).with_capacity(24.0, demands.clone());
+        let dmatrix = dmatrix.with_fleet(fleet);
+        let summary = Solver::builder()
+            .params(Params {
+                number_of_states: 8,
+                number_of_repeats: 2,
+                ..Params::default()
+            })
+            .stop(Stop::after_iterations(1000))
+            .seed(3)
+            .solve(&dmatrix)
+            .unwrap();
+        assert!(summary.feasible, "loads {:?}", summary.loads);
+        assert!(summary.routes.len() <= 4);
+        let mut visited = summary.routes.concat();
+        visited.sort();
+        assert_eq!(visited, (1..size).collect::<Vec<_>>());
+        let mut cost = 0;
+        for (route, load) in summary.routes.iter().zip(&summary.loads) {
+            assert!(*load <= 24.0);
+            let demand: f64 = route.iter().map(|&city| demands[city]).sum();
+            assert_eq!(demand, *load);
+            let mut stops = vec![0];
+            stops.extend(route);
+            stops.push(0);
+            cost += stops
+                .windows(2)
+                .map(|arc| dmatrix.cost(arc[0], arc[1]))
+                .sum::<i64>();
+        }
+        assert_eq!(summary.best.cost, cost);
+    }
+
+    #[test]
+    fn test_branch_and_bound_keeps_the_time_limit() {
+        // Points in the plane: the symmetric arcs make the assignment bounds
+        // weak and the search tree huge
+        let size = 60;
+        let point = |i: usize| (((i * 37) % 101) as f64, ((i * 53) % 97) as f64);
+        let matrix: Vec<Vec<i64>> = (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| {
+                        let ((x1, y1), (x2, y2)) = (point(i), point(j));
+                        (x1 - x2).hypot(y1 - y2).round() as i64
+                    })
+                    .collect()
+            })
+            .collect();
+        let dmatrix = DistanceMatrix::new(matrix);
+        let summary = Solver::builder()
+            .params(Params {
+                number_of_states: 8,
+                number_of_repeats: 2,
+                exact_solver_max_size: size,
+                ..Params::default()
+            })
+            .stop(Stop::after(Duration::from_secs(1)))
+            .solve(&dmatrix)
+            .unwrap();
+        assert!(summary.elapsed < Duration::from_secs(3));
+        let mut path = summary.best.solution.path.clone();
+        path.sort();
+        assert_eq!(path, (0..size).collect::<Vec<usize>>());
+        assert_eq!(summary.best.cost, summary.best.solution.cost(&dmatrix));
+    }
+
+    #[test]
+    fn test_generalized_tour_is_optimal() {
+        // 8 clusters of 3 points in the plane, the cluster of city i is i % 8
+        let (clusters, size) = (8, 24);
+        let point = |i: usize| (((i * 37) % 101) as f64, ((i * 53) % 97) as f64);
+        let matrix: Vec<Vec<i64>> = (0..size)
+            .map(|i| {
+                (0..size)
+                    .map(|j| {
+                        let ((x1, y1), (x2, y2)) = (point(i), point(j));
+                        (x1 - x2).hypot(y1 - y2).round() as i64
+                    })
+                    .collect()
+            })
+            .collect();
+        let members = (0..clusters)
+            .map(|cluster| (cluster..size).step_by(clusters).collect())
+            .collect();
+        let clusters_of = Clusters::new(members);
+        let dmatrix = DistanceMatrix::new(matrix).with_clusters(clusters_of.clone());
+        // Every order of the clusters after cluster 0, at its cheapest cities
+        let mut optimum = i64::MAX;
+        let mut orders = vec![vec![0]];
+        while let Some(order) = orders.pop() {
+            if order.len() == clusters {
+                let tour = clusters_of.choose(&dmatrix, &Solution::new(order));
+                optimum = optimum.min(tour.cost(&dmatrix));
+                continue;
+            }
+            for next in (1..clusters).filter(|next| !order.contains(next)) {
+                let mut longer = order.clone();
+                longer.push(next);
+                orders.push(longer);
+            }
+        }
+        let summary = Solver::builder()
+            .params(Params {
+                number_of_states: 8,
+                number_of_repeats: 2,
+                local_search_period: 5,
+                crossover_period: 5,
+                ..Params::default()
+            })
+            .stop(Stop::after_iterations(300))
+            .seed(7)
+            .solve(&dmatrix)
+            .unwrap();
+        let mut visited = clusters_of.order(&summary.best.solution).path;
+        visited.sort();
+        assert_eq!(visited, (0..clusters).collect::<Vec<_>>());
+        assert_eq!(summary.best.cost, summary.best.solution.cost(&dmatrix));
+        assert_eq!(summary.best.cost, optimum);
+        assert!(summary.lower_bound <= optimum as f64);
+    }
+}
@@ -1,17 +1,89 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use super::{
+    cost::Cost,
+    crossover::Crossover,
+    fleet::Loads,
     helpers::acceptance,
+    local_search::LocalSearch,
     matrix::DistanceMatrix,
-    solution::{ComputedSolution, Solution},
+    moves::Move,
+    precedence::PrecedenceCheck,
+    random::rng,
+    solution::Solution,
+    tabu::{TabuList, TabuStats},
     temp::TemperatureBounds,
+    time_window::Schedule,
 };
 
+/// Violations up to this are rounding of the incremental sums, the
+/// constraints hold
+pub const TOLERANCE: f64 = 1e-9;
+
+/// How far the tour of a replica is from meeting the time windows and the
+/// vehicle capacities, kept up to date move by move
+#[derive(Debug, Clone)]
+struct Violations {
+    schedule: Option<Schedule>,
+    loads: Option<Loads>,
+}
+
+impl Violations {
+    /// `None` for an instance without soft constraints
+    fn new<C: Cost>(dmatrix: &DistanceMatrix<C>, solution: &Solution) -> Option<Self> {
+        let schedule = dmatrix
+            .time_windows
+            .as_ref()
+            .map(|windows| Schedule::new(windows, dmatrix, solution));
+        let loads = dmatrix
+            .fleet
+            .as_ref()
+            .filter(|fleet| fleet.capacity.is_some())
+            .map(|fleet| Loads::new(fleet, solution));
+        (schedule.is_some() || loads.is_some()).then_some(Violations { schedule, loads })
+    }
+
+    /// Lateness and overload together
+    fn total(&self) -> f64 {
+        self.schedule
+            .as_ref()
+            .map_or(0.0, |schedule| schedule.lateness)
+            + self.loads.as_ref().map_or(0.0, |loads| loads.overload)
+    }
+
+    /// The total after the move was applied, kept by `commit`
+    fn propose<C: Cost>(
+        &mut self,
+        dmatrix: &DistanceMatrix<C>,
+        proposal: &Move,
+        solution: &Solution,
+    ) -> f64 {
+        let mut total = 0.0;
+        if let (Some(schedule), Some(windows)) = (&mut self.schedule, &dmatrix.time_windows) {
+            total += schedule.propose(windows, dmatrix, proposal, solution);
+        }
+        if let (Some(loads), Some(fleet)) = (&mut self.loads, &dmatrix.fleet) {
+            total += loads.propose(fleet, proposal, solution);
+        }
+        total
+    }
+
+    fn commit(&mut self) {
+        if let Some(schedule) = &mut self.schedule {
+            schedule.commit();
+        }
+        if let Some(loads) = &mut self.loads {
+            loads.commit();
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct State {
     pub solution: Solution,
-    pub temperature: f64,
     pub is_shuffle_transition: bool,
+    /// Arcs the replica may not change back yet, `None` without a tenure
+    pub tabu: Option<TabuList>,
 }
 
 impl State {
@@ -19,82 +91,179 @@ impl State {
         self.solution.size
     }
 
-    pub fn mutate_state(&mut self, max_temp: f64, max_percent: f64) {
+    /// Draws a random move, longer at higher temperatures
+    pub fn propose(&self, temperature: f64, max_temp: f64, max_percent: f64) -> Move {
         let n = self.size();
-        let ratio = self.temperature / max_temp;
+        let ratio = f64::min(temperature / max_temp, 1.0);
         let trans_length: usize = (n as f64 * max_percent * ratio).ceil() as usize;
         if self.is_shuffle_transition {
-            let start = thread_rng().gen_range(0..n);
-            self.solution.shuffle(start, trans_length);
+            let start = rng().gen_range(0..n);
+            Move::Shuffle {
+                start,
+                length: usize::min(trans_length, n),
+            }
         } else {
             // I hate this solution but it is O(1) on average
-            let first_index: usize = thread_rng().gen_range(0..n);
-            let mut second_index: usize;
+            let first: usize = rng().gen_range(0..n);
+            let mut second: usize;
             loop {
-                second_index = thread_rng().gen_range(0..n);
-                if second_index != first_index {
+                second = rng().gen_range(0..n);
+                if second != first {
                     break;
                 }
             }
 
-            self.solution
-                .swap_parts(first_index, second_index, trans_length);
+            // Two disjoint segments have to fit in the cycle
+            Move::Swap {
+                first,
+                second,
+                length: usize::min(trans_length, n / 2),
+            }
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct StatesContainer<'a> {
+pub struct StatesContainer<'a, C: Cost = f64> {
     pub temp_bounds: TemperatureBounds,
-    pub distance_matrix: &'a DistanceMatrix,
+    pub distance_matrix: &'a DistanceMatrix<C>,
 
     pub states: Vec<State>,
-    pub costs: Vec<f64>,
+    pub costs: Vec<C>,
+
+    /// Temperature ladder, indexed by slot
+    pub temperatures: Vec<f64>,
+    /// Slot of the temperature held by each replica
+    slot_of_replica: Vec<usize>,
+    /// Replica holding each temperature slot, inverse of `slot_of_replica`
+    replica_of_slot: Vec<usize>,
 
-    pub best_cost: f64,
+    pub best_cost: C,
     pub best_solution: Option<Solution>,
+    /// Lateness and overload of the best tour, which is the cheapest of the
+    /// least violating
+    pub best_violation: f64,
+
+    /// Violations of the tour of each replica, empty without time windows
+    /// or capacities
+    violations: Vec<Violations>,
+    /// Cost of a unit of lateness or overload in the acceptance of the moves
+    pub penalty_weight: f64,
+    /// Share of the proposals choosing another city of a cluster, for a
+    /// generalized TSP
+    pub probability_of_choice: f64,
+    /// Cheapest arcs between the clusters of a generalized TSP, whose cluster
+    /// orders the replicas are crossed on
+    cluster_matrix: Option<DistanceMatrix<C>>,
+
+    /// Window saved by the last shuffle proposal, reused between proposals
+    backup: Vec<usize>,
+    /// Arcs of the last proposal before and after applying it, for the tabu lists
+    removed_arcs: Vec<(usize, usize)>,
+    added_arcs: Vec<(usize, usize)>,
+    /// Keeps the replicas of a sequential ordering problem feasible
+    precedence_check: Option<PrecedenceCheck>,
 }
 
-impl<'a> StatesContainer<'a> {
+impl<'a, C: Cost> StatesContainer<'a, C> {
     pub fn new(
         temp_bounds: TemperatureBounds,
-        distance_matrix: &DistanceMatrix,
-    ) -> StatesContainer {
+        distance_matrix: &DistanceMatrix<C>,
+    ) -> StatesContainer<'_, C> {
         StatesContainer {
             temp_bounds,
             distance_matrix,
             states: vec![],
             costs: vec![],
+            temperatures: vec![],
+            slot_of_replica: vec![],
+            replica_of_slot: vec![],
 
-            best_cost: f64::INFINITY,
+            best_cost: C::INFINITY,
             best_solution: None,
+            best_violation: f64::INFINITY,
+            violations: vec![],
+            penalty_weight: 1.0,
+            probability_of_choice: 0.0,
+            cluster_matrix: distance_matrix
+                .clusters
+                .as_ref()
+                .map(|clusters| clusters.matrix(distance_matrix)),
+            backup: vec![],
+            removed_arcs: vec![],
+            added_arcs: vec![],
+            // Precedences come on closed paths, whose order starts after the
+            // last, dummy city
+            precedence_check: distance_matrix
+                .precedences
+                .as_ref()
+                .map(|_| PrecedenceCheck::new(distance_matrix.size, distance_matrix.size - 1)),
         }
     }
 
     fn size(&self) -> usize {
-        self.distance_matrix.size
+        self.distance_matrix.tour_size()
     }
 
-    pub fn add(&mut self, state: State) {
+    /// Adds a replica together with a new slot on the temperature ladder
+    pub fn add(&mut self, state: State, temperature: f64) {
         assert!(state.size() == self.size());
 
         let cost = state.solution.cost(self.distance_matrix);
-        if cost < self.best_cost {
+        let mut violation = 0.0;
+        if let Some(violations) = Violations::new(self.distance_matrix, &state.solution) {
+            violation = violations.total();
+            self.violations.push(violations);
+        }
+        if self.beats_best(cost, violation) {
             self.best_cost = cost;
+            self.best_violation = violation;
             self.best_solution = Some(state.solution.clone());
         }
 
+        self.slot_of_replica.push(self.temperatures.len());
+        self.replica_of_slot.push(self.states.len());
+        self.temperatures.push(temperature);
         self.states.push(state);
         self.costs.push(cost);
     }
 
+    /// Current temperature of the replica
+    #[inline]
+    pub fn temperature(&self, replica: usize) -> f64 {
+        self.temperatures[self.slot_of_replica[replica]]
+    }
+
+    /// Replica currently holding the temperature slot
+    #[inline]
+    pub fn replica_at(&self, slot: usize) -> usize {
+        self.replica_of_slot[slot]
+    }
+
+    /// Temperatures from the coldest with the cost of the tour holding each
+    pub fn ladder(&self) -> Vec<(f64, C)> {
+        let mut slots: Vec<usize> = (0..self.temperatures.len()).collect();
+        slots.sort_by(|&a, &b| self.temperatures[a].total_cmp(&self.temperatures[b]));
+        slots
+            .into_iter()
+            .map(|slot| (self.temperatures[slot], self.costs[self.replica_at(slot)]))
+            .collect()
+    }
+
     pub fn cool(&mut self, cooling_rate: f64) {
-        self.states.iter_mut().for_each(|state| {
-            let new_temperatue = state.temperature * cooling_rate;
-            state.temperature = f64::max(new_temperatue, self.temp_bounds.min);
+        let min = self.temp_bounds.min;
+        self.temperatures.iter_mut().for_each(|temperature| {
+            *temperature = f64::max(*temperature * cooling_rate, min);
         })
     }
 
+    /// Exchanges the temperatures held by two replicas
+    fn swap_temperatures(&mut self, first: usize, second: usize) {
+        self.slot_of_replica.swap(first, second);
+        self.replica_of_slot[self.slot_of_replica[first]] = first;
+        self.replica_of_slot[self.slot_of_replica[second]] = second;
+    }
+
     // pub fn best_solutions(self, n: usize) -> Vec<ComputedSolution> {
     //     assert!(n < self.states.len());
     //     let mut wrapped: Vec<ComputedSolution> = self
@@ -110,35 +279,255 @@ impl<'a> StatesContainer<'a> {
     //     wrapped.into_iter().take(n).collect()
     // }
 
+    /// Lateness and overload of the tour of the replica, zero without time
+    /// windows or capacities
+    #[inline]
+    pub fn violation(&self, replica: usize) -> f64 {
+        self.violations
+            .get(replica)
+            .map_or(0.0, |violations| violations.total())
+    }
+
+    /// Whether a tour is better than the best one: violating less, or as
+    /// much and cheaper
+    #[inline]
+    fn beats_best(&self, cost: C, violation: f64) -> bool {
+        beats(cost, violation, self.best_cost, self.best_violation)
+    }
+
+    /// Makes violations costlier while fewer than half of the replicas meet
+    /// the constraints and cheaper otherwise, within a factor 1000 of `initial`;
+    /// without time windows or capacities there is nothing to weigh
+    pub fn adapt_penalty(&mut self, initial: f64, adaptation: f64) {
+        if self.violations.is_empty() {
+            return;
+        }
+        let feasible = self
+            .violations
+            .iter()
+            .filter(|violations| violations.total() <= TOLERANCE)
+            .count();
+        let factor = if 2 * feasible < self.violations.len() {
+            1.0 + adaptation
+        } else {
+            1.0 / (1.0 + adaptation)
+        };
+        self.penalty_weight = (self.penalty_weight * factor).clamp(initial / 1e3, initial * 1e3);
+    }
+
+    /// What the tabu lists of the replicas ruled on so far
+    pub fn tabu_stats(&self) -> TabuStats {
+        self.states
+            .iter()
+            .filter_map(|state| state.tabu.as_ref())
+            .fold(TabuStats::default(), |total, tabu| total + tabu.stats)
+    }
+
+    /// One proposal per replica, evaluated by the change of the touched
+    /// arcs and undone in place when rejected or tabu
     pub fn metropolis_tranision(&mut self, max_percent_of_cycle: f64) {
-        for (state, cost) in self.states.iter_mut().zip(self.costs.iter_mut()) {
-            let mut new_state = state.clone();
-            new_state.mutate_state(self.temp_bounds.max, max_percent_of_cycle);
-            let new_cost = new_state.solution.cost(self.distance_matrix);
-
-            if acceptance(*cost, new_cost, new_state.temperature) {
-                *state = new_state;
-                *cost = new_cost;
+        let dmatrix = self.distance_matrix;
+        for replica in 0..self.states.len() {
+            let temperature = self.temperature(replica);
+            let state = &mut self.states[replica];
+            let choice = match &dmatrix.clusters {
+                Some(clusters) if rng().gen::<f64>() < self.probability_of_choice => {
+                    clusters.propose(&state.solution)
+                }
+                _ => None,
+            };
+            let proposal = choice.unwrap_or_else(|| {
+                state.propose(temperature, self.temp_bounds.max, max_percent_of_cycle)
+            });
+
+            let removed = proposal.arcs_cost(&state.solution, dmatrix);
+            if state.tabu.is_some() {
+                proposal.arcs(&state.solution, &mut self.removed_arcs);
+            }
+            proposal.apply(&mut state.solution, &mut self.backup);
+            if let (Some(precedences), Some(check)) =
+                (&dmatrix.precedences, &mut self.precedence_check)
+            {
+                if !check.allows(precedences, &proposal, &state.solution) {
+                    proposal.undo(&mut state.solution, &self.backup);
+                    continue;
+                }
+            }
+            let added = proposal.arcs_cost(&state.solution, dmatrix);
+            let cost = self.costs[replica];
+            let new_cost = cost - removed + added;
+            // Time windows and loads are summed again from the first moved city
+            let (violation, new_violation) = match self.violations.get_mut(replica) {
+                Some(violations) => (
+                    violations.total(),
+                    violations.propose(dmatrix, &proposal, &state.solution),
+                ),
+                None => (0.0, 0.0),
+            };
+
+            if let Some(tabu) = &mut state.tabu {
+                proposal.arcs(&state.solution, &mut self.added_arcs);
+                let improves_best =
+                    beats(new_cost, new_violation, self.best_cost, self.best_violation);
+                if !tabu.allows(&self.removed_arcs, &self.added_arcs, improves_best) {
+                    proposal.undo(&mut state.solution, &self.backup);
+                    continue;
+                }
+            }
+
+            let weight = self.penalty_weight;
+            if acceptance(
+                cost.to_f64() + weight * violation,
+                new_cost.to_f64() + weight * new_violation,
+                temperature,
+            ) {
+                self.costs[replica] = new_cost;
+                if let Some(violations) = self.violations.get_mut(replica) {
+                    violations.commit();
+                }
+                if let Some(tabu) = &mut state.tabu {
+                    tabu.record(&self.removed_arcs, &self.added_arcs);
+                }
+            } else {
+                proposal.undo(&mut state.solution, &self.backup);
             }
         }
 
-        for (i, cost) in self.costs.iter().enumerate() {
-            if *cost < self.best_cost {
-                self.best_cost = *cost;
-                self.best_solution = Some(self.states[i].solution.clone());
+        for i in 0..self.states.len() {
+            if self.beats_best(self.costs[i], self.violation(i)) {
+                // Deltas of float costs and violations drift, the recorded
+                // best is recomputed
+                let solution = &self.states[i].solution;
+                self.costs[i] = solution.cost(dmatrix);
+                if let Some(violations) = Violations::new(dmatrix, solution) {
+                    self.violations[i] = violations;
+                }
+                let violation = self.violation(i);
+                if self.beats_best(self.costs[i], violation) {
+                    self.best_cost = self.costs[i];
+                    self.best_violation = violation;
+                    self.best_solution = Some(self.states[i].solution.clone());
+                }
             }
         }
     }
 
+    /// Runs the local search on the cheapest replica of the least violating
+    /// ones, returns by how much its cost went down; a polish breaking
+    /// precedences or violating the time windows or capacities more is dropped
+    pub fn polish_best(&mut self, local_search: &LocalSearch<C>) -> C {
+        let best_index = (0..self.states.len())
+            .min_by(|&a, &b| {
+                self.violation(a)
+                    .total_cmp(&self.violation(b))
+                    .then(self.costs[a].total_cmp(&self.costs[b]))
+            })
+            .unwrap();
+        let dmatrix = self.distance_matrix;
+        let violation = self.violation(best_index);
+        let solution = &mut self.states[best_index].solution;
+        let constrained = self.precedence_check.is_some() || !self.violations.is_empty();
+        let before = constrained.then(|| solution.clone());
+        let mut improvement = local_search.polish(solution);
+        if let Some(before) = before {
+            let breaks_precedences = match (&dmatrix.precedences, &self.precedence_check) {
+                (Some(precedences), Some(check)) => !check.holds(precedences, solution),
+                _ => false,
+            };
+            let violations = Violations::new(dmatrix, solution);
+            let worse = violations
+                .as_ref()
+                .is_some_and(|violations| violations.total() > violation + TOLERANCE);
+            if breaks_precedences || worse {
+                *solution = before;
+                improvement = C::ZERO;
+            } else if let Some(violations) = violations {
+                self.violations[best_index] = violations;
+            }
+        }
+        self.costs[best_index] = self.costs[best_index] - improvement;
+        let violation = self.violation(best_index);
+        if self.beats_best(self.costs[best_index], violation) {
+            self.best_cost = self.costs[best_index];
+            self.best_violation = violation;
+            self.best_solution = Some(self.states[best_index].solution.clone());
+        }
+        improvement
+    }
+
+    /// Crosses two replicas of the cheaper half and puts the offspring in
+    /// place of the costliest replica of the hotter half of the ladder
+    pub fn recombine(&mut self, crossover: Crossover) {
+        let n = self.states.len();
+        let mut by_cost: Vec<usize> = (0..n).collect();
+        by_cost.sort_by(|&a, &b| self.costs[a].total_cmp(&self.costs[b]));
+        let pool = usize::max(n / 2, 2);
+        let rng = &mut rng();
+        let first = by_cost[rng.gen_range(0..pool)];
+        let second = loop {
+            let second = by_cost[rng.gen_range(0..pool)];
+            if second != first {
+                break second;
+            }
+        };
+
+        let mut slots: Vec<usize> = (0..n).collect();
+        slots.sort_by(|&a, &b| self.temperatures[a].total_cmp(&self.temperatures[b]));
+        let victim = slots[n / 2..]
+            .iter()
+            .map(|&slot| self.replica_at(slot))
+            .filter(|&replica| replica != first && replica != second)
+            .max_by(|&a, &b| self.costs[a].total_cmp(&self.costs[b]));
+        let Some(victim) = victim else {
+            return;
+        };
+
+        let (first, second) = (&self.states[first].solution, &self.states[second].solution);
+        let mut offspring = match (&self.distance_matrix.clusters, &self.cluster_matrix) {
+            // Cluster orders are crossed and visited at their cheapest cities
+            (Some(clusters), Some(cluster_matrix)) => {
+                let order = crossover.cross(
+                    &clusters.order(first),
+                    &clusters.order(second),
+                    cluster_matrix,
+                );
+                clusters.choose(self.distance_matrix, &order)
+            }
+            _ => crossover.cross(first, second, self.distance_matrix),
+        };
+        if let (Some(precedences), Some(check)) =
+            (&self.distance_matrix.precedences, &self.precedence_check)
+        {
+            check.repair(precedences, &mut offspring);
+        }
+        let cost = offspring.cost(self.distance_matrix);
+        let mut violation = 0.0;
+        if let Some(violations) = Violations::new(self.distance_matrix, &offspring) {
+            violation = violations.total();
+            self.violations[victim] = violations;
+        }
+        if self.beats_best(cost, violation) {
+            self.best_cost = cost;
+            self.best_violation = violation;
+            self.best_solution = Some(offspring.clone());
+        }
+        let state = &mut self.states[victim];
+        state.solution = offspring;
+        if let Some(tabu) = &mut state.tabu {
+            tabu.clear();
+        }
+        self.costs[victim] = cost;
+    }
+
     pub fn replica_transition(&mut self, swap_probabilty: f64, closeness: f64) {
         assert!(self.states.len() >= 2);
 
         // I hate this solution but it is O(1) on average
         let n = self.states.len();
-        let mut first_index: usize = thread_rng().gen_range(0..n);
+        let mut first_index: usize = rng().gen_range(0..n);
         let mut second_index: usize;
         loop {
-            second_index = thread_rng().gen_range(0..n);
+            second_index = rng().gen_range(0..n);
             if second_index != first_index {
                 break;
             }
@@ -147,22 +536,130 @@ impl<'a> StatesContainer<'a> {
             std::mem::swap(&mut first_index, &mut second_index);
         }
 
-        let cost_upper_bound = closeness * self.best_cost;
-        let first_cost_to_much = self.costs[first_index] > cost_upper_bound;
-        let second_cost_to_much = self.costs[second_index] > cost_upper_bound;
+        let cost_upper_bound = closeness * self.best_cost.to_f64();
+        let first_cost_to_much = self.costs[first_index].to_f64() > cost_upper_bound;
+        let second_cost_to_much = self.costs[second_index].to_f64() > cost_upper_bound;
 
-        if first_cost_to_much && second_cost_to_much {
-            // Create raw pointers to swap
-            let first_temp: *mut f64 = &mut self.states[first_index].temperature as *mut f64;
-            let second_temp: *mut f64 = &mut self.states[second_index].temperature as *mut f64;
+        // Pick at random with given prob
+        if first_cost_to_much && second_cost_to_much && rng().gen_range(0.0..1.0) < swap_probabilty
+        {
+            self.swap_temperatures(first_index, second_index);
+        }
+    }
+}
 
-            let mut rng = thread_rng();
-            // Pick at random with given prob
-            if rng.gen_range(0.0..1.0) < swap_probabilty {
-                unsafe {
-                    core::ptr::swap(first_temp, second_temp);
-                }
-            }
+/// Whether a tour of the given cost and violation is better than the best:
+/// violating less, or as much and cheaper
+#[inline]
+fn beats<C: Cost>(cost: C, violation: f64, best_cost: C, best_violation: f64) -> bool {
+    if violation < best_violation - TOLERANCE {
+        return true;
+    }
+    violation <= best_violation + TOLERANCE && cost < best_cost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temperature_swap_keeps_ladder() {
+        let dmatrix = DistanceMatrix::new(vec![vec![1.0; 4]; 4]);
+        let bounds = TemperatureBounds {
+            max: 10.0,
+            min: 1.0,
+        };
+        let mut states = StatesContainer::new(bounds, &dmatrix);
+        for temperature in [2.0, 4.0, 8.0] {
+            let state = State {
+                solution: Solution::random_solution(4),
+                is_shuffle_transition: false,
+                tabu: None,
+            };
+            states.add(state, temperature);
+        }
+        // Every tour costs 4, so any pair is far enough from the best
+        states.best_cost = 1.0;
+        states.replica_transition(1.0, 1.5);
+        let mut held: Vec<f64> = (0..3).map(|replica| states.temperature(replica)).collect();
+        assert_ne!(held, vec![2.0, 4.0, 8.0]);
+        for slot in 0..3 {
+            assert_eq!(
+                states.temperature(states.replica_at(slot)),
+                states.temperatures[slot]
+            );
+        }
+        held.sort_by(f64::total_cmp);
+        assert_eq!(held, vec![2.0, 4.0, 8.0]);
+    }
+
+    #[test]
+    fn test_recombine_replaces_a_hot_replica() {
+        let size = 12;
+        let dmatrix = DistanceMatrix::new(
+            (0..size)
+                .map(|i| {
+                    (0..size)
+                        .map(|j| ((i * 7 + j * 5) % 11) as f64 + 1.0)
+                        .collect()
+                })
+                .collect(),
+        );
+        let bounds = TemperatureBounds {
+            max: 10.0,
+            min: 1.0,
+        };
+        let mut states = StatesContainer::new(bounds, &dmatrix);
+        for temperature in [1.0, 2.0, 4.0, 8.0] {
+            let state = State {
+                solution: Solution::random_solution(size),
+                is_shuffle_transition: false,
+                tabu: None,
+            };
+            states.add(state, temperature);
+        }
+        let before: Vec<f64> = states.costs.clone();
+        states.recombine(Crossover::EdgeAssembly);
+        let changed: Vec<usize> = (0..4).filter(|&r| states.costs[r] != before[r]).collect();
+        assert!(changed.len() <= 1);
+        for replica in 0..4 {
+            let state = &states.states[replica];
+            assert_eq!(states.costs[replica], state.solution.cost(&dmatrix));
+        }
+        for &replica in changed.iter() {
+            assert!(states.temperature(replica) >= 4.0);
+        }
+        assert!(states.best_cost <= states.costs.iter().cloned().fold(f64::INFINITY, f64::min));
+    }
+
+    #[test]
+    fn test_tabu_blocks_reverting_moves() {
+        let size = 30;
+        let dmatrix = DistanceMatrix::<i64>::modular(size, [7, 5, 0, 11]);
+        let bounds = TemperatureBounds {
+            max: 0.01,
+            min: 0.01,
+        };
+        let mut states = StatesContainer::new(bounds, &dmatrix);
+        for is_shuffle_transition in [false, true] {
+            let state = State {
+                solution: Solution::random_solution(size),
+                is_shuffle_transition,
+                tabu: Some(TabuList::new(50)),
+            };
+            states.add(state, 0.01);
+        }
+        for _ in 0..2000 {
+            states.metropolis_tranision(0.1);
+        }
+        let stats = states.tabu_stats();
+        assert_eq!(stats.checked, 4000);
+        assert!(stats.blocked > 0);
+        for replica in 0..2 {
+            assert_eq!(
+                states.costs[replica],
+                states.states[replica].solution.cost(&dmatrix)
+            );
         }
     }
 }
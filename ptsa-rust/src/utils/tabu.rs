@@ -0,0 +1,153 @@
+use std::{collections::HashMap, ops::Add};
+
+use serde::Serialize;
+
+/// How the tabu lists of a run ruled on the proposals they saw
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TabuStats {
+    /// Proposals checked against a tabu list
+    pub checked: u64,
+    /// Proposals rejected for touching a tabu arc
+    pub blocked: u64,
+    /// Proposals touching a tabu arc let through for beating the best cost
+    pub aspirated: u64,
+}
+
+impl TabuStats {
+    /// Blocked proposals as a fraction of the checked ones
+    pub fn blocked_ratio(&self) -> f64 {
+        if self.checked == 0 {
+            return 0.0;
+        }
+        self.blocked as f64 / self.checked as f64
+    }
+}
+
+impl Add for TabuStats {
+    type Output = TabuStats;
+
+    fn add(self, other: TabuStats) -> TabuStats {
+        TabuStats {
+            checked: self.checked + other.checked,
+            blocked: self.blocked + other.blocked,
+            aspirated: self.aspirated + other.aspirated,
+        }
+    }
+}
+
+/// Short-term memory of the arcs changed by the accepted moves of a replica.
+/// A move touching one of them, adding an arc recently removed or removing
+/// one recently added, is tabu for `tenure` proposals.
+#[derive(Debug, Clone)]
+pub struct TabuList {
+    pub tenure: usize,
+    /// Proposals seen so far
+    clock: usize,
+    /// Last proposal each arc is tabu for
+    expiry: HashMap<(usize, usize), usize>,
+    pub stats: TabuStats,
+}
+
+impl TabuList {
+    pub fn new(tenure: usize) -> Self {
+        TabuList {
+            tenure,
+            clock: 0,
+            expiry: HashMap::new(),
+            stats: TabuStats::default(),
+        }
+    }
+
+    /// Counts a proposal changing `removed` into `added`, true when it may be
+    /// evaluated: none of its arcs is tabu, or it beats the best cost
+    pub fn allows(
+        &mut self,
+        removed: &[(usize, usize)],
+        added: &[(usize, usize)],
+        improves_best: bool,
+    ) -> bool {
+        self.clock += 1;
+        self.stats.checked += 1;
+        let tabu = changed(removed, added).any(|arc| self.is_tabu(arc));
+        if !tabu {
+            return true;
+        }
+        if improves_best {
+            self.stats.aspirated += 1;
+            return true;
+        }
+        self.stats.blocked += 1;
+        false
+    }
+
+    /// Makes the arcs changed by an accepted move tabu
+    pub fn record(&mut self, removed: &[(usize, usize)], added: &[(usize, usize)]) {
+        let until = self.clock + self.tenure;
+        for arc in changed(removed, added) {
+            self.expiry.insert(arc, until);
+        }
+        // Expired arcs are dropped once the map outgrows a few tenures of moves
+        if self.expiry.len() > 8 * self.tenure {
+            let clock = self.clock;
+            self.expiry.retain(|_, until| *until >= clock);
+        }
+    }
+
+    /// Forgets every arc, for a replica whose tour was replaced
+    pub fn clear(&mut self) {
+        self.expiry.clear();
+    }
+
+    fn is_tabu(&self, arc: (usize, usize)) -> bool {
+        self.expiry
+            .get(&arc)
+            .is_some_and(|&until| until >= self.clock)
+    }
+}
+
+/// Arcs in only one of the lists, a shuffle can leave some in place
+fn changed<'a>(
+    removed: &'a [(usize, usize)],
+    added: &'a [(usize, usize)],
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let only_removed = removed.iter().filter(|arc| !added.contains(arc));
+    let only_added = added.iter().filter(|arc| !removed.contains(arc));
+    only_removed.chain(only_added).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undoing_a_move_is_tabu_until_the_tenure_ends() {
+        let mut tabu = TabuList::new(3);
+        let removed = [(0, 1), (2, 3)];
+        let added = [(0, 2), (1, 3)];
+        assert!(tabu.allows(&removed, &added, false));
+        tabu.record(&removed, &added);
+
+        // The inverse move puts the removed arcs back
+        assert!(!tabu.allows(&added, &removed, false));
+        assert!(tabu.allows(&added, &removed, true));
+        assert!(!tabu.allows(&added, &removed, false));
+        assert!(tabu.allows(&added, &removed, false));
+        assert_eq!(
+            tabu.stats,
+            TabuStats {
+                checked: 5,
+                blocked: 2,
+                aspirated: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_unchanged_arcs_are_not_recorded() {
+        let mut tabu = TabuList::new(10);
+        tabu.allows(&[(0, 1), (1, 2)], &[(0, 1), (1, 3)], false);
+        tabu.record(&[(0, 1), (1, 2)], &[(0, 1), (1, 3)]);
+        assert!(tabu.allows(&[(0, 1)], &[(0, 4)], false));
+        assert!(!tabu.allows(&[(1, 3)], &[(1, 2)], false));
+    }
+}
@@ -1,6 +1,7 @@
-use rand::thread_rng;
 use rand_distr::{Beta, Distribution};
 
+use super::random::rng;
+
 #[derive(Debug, Clone)]
 pub struct TemperatureBounds {
     pub max: f64,
@@ -8,9 +9,34 @@ pub struct TemperatureBounds {
 }
 
 impl TemperatureBounds {
+    /// Beta(a, b) distributed in [0, 1], uniform for a = b = 1
     #[inline]
     pub fn random_temperature(&self, a: f64, b: f64) -> f64 {
-        let beta = Beta::new(a, b).unwrap();
-        beta.sample_iter(&mut thread_rng()).next().unwrap()
+        let beta = Beta::new(a, b).expect("Beta shape parameters are validated to be positive");
+        beta.sample_iter(&mut rng()).next().unwrap()
+    }
+
+    /// Beta(a, b) distributed between the bounds, for the automatic mode
+    /// whose bounds come from the cost changes of the instance
+    #[inline]
+    pub fn random_temperature_within(&self, a: f64, b: f64) -> f64 {
+        self.min + self.random_temperature(a, b) * (self.max - self.min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_automatic_mode_uses_the_bounds() {
+        let bounds = TemperatureBounds {
+            max: 50.0,
+            min: 10.0,
+        };
+        for _ in 0..100 {
+            assert!((0.0..=1.0).contains(&bounds.random_temperature(1.0, 1.0)));
+            assert!((10.0..=50.0).contains(&bounds.random_temperature_within(1.0, 1.0)));
+        }
     }
 }
@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cost::Cost, matrix::DistanceMatrix, moves::Move, solution::Solution,
+    validation::ValidationError,
+};
+
+/// When a city is served: starting no earlier than `ready` and no later than
+/// `due`, for `service` time units
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub ready: f64,
+    pub due: f64,
+    pub service: f64,
+}
+
+impl TimeWindow {
+    /// Any time, without service, for the dummy city of a closed path
+    pub const OPEN: TimeWindow = TimeWindow {
+        ready: 0.0,
+        due: f64::INFINITY,
+        service: 0.0,
+    };
+}
+
+/// Time windows of an ATSPTW. Travelling an arc takes its cost, arriving
+/// early waits for the window to open and arriving late is lateness.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeWindows {
+    pub windows: Vec<TimeWindow>,
+    /// City the schedule leaves at time zero and comes back to: the first
+    /// city of a cycle, the dummy city of a closed path
+    pub origin: usize,
+}
+
+impl TimeWindows {
+    pub fn new(windows: Vec<TimeWindow>) -> Self {
+        TimeWindows { windows, origin: 0 }
+    }
+
+    pub fn size(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn validate(&self, size: usize) -> Result<(), ValidationError> {
+        let invalid = |message: String| Err(ValidationError::Matrix(message));
+        if self.size() != size {
+            return invalid(format!(
+                "time windows are given for {} cities, the matrix has {}",
+                self.size(),
+                size
+            ));
+        }
+        for (city, window) in self.windows.iter().enumerate() {
+            let TimeWindow {
+                ready,
+                due,
+                service,
+            } = *window;
+            if !ready.is_finite() || ready < 0.0 || due.is_nan() || due < ready {
+                return invalid(format!(
+                    "city {} has the time window [{}, {}]",
+                    city, ready, due
+                ));
+            }
+            if !service.is_finite() || service < 0.0 {
+                return invalid(format!("city {} has the service time {}", city, service));
+            }
+        }
+        Ok(())
+    }
+
+    /// The windows of the closed instance of a path, whose dummy city, the
+    /// last one, is the origin
+    pub fn closed(&self) -> Self {
+        let mut windows = self.windows.clone();
+        windows.push(TimeWindow::OPEN);
+        TimeWindows {
+            origin: windows.len() - 1,
+            windows,
+        }
+    }
+
+    /// Lateness of every city along the tour, the origin's when coming back
+    pub fn lateness<C: Cost>(&self, dmatrix: &DistanceMatrix<C>, solution: &Solution) -> Vec<f64> {
+        let n = solution.size;
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == self.origin)
+            .unwrap();
+        let mut lateness = vec![0.0; n];
+        let mut time = self.departure(self.origin, 0.0);
+        for k in 1..=n {
+            let (from, to) = (solution.path[(at + k - 1) % n], solution.path[(at + k) % n]);
+            let arrival = time + dmatrix.cost(from, to).to_f64();
+            lateness[to] = f64::max(arrival - self.windows[to].due, 0.0);
+            time = self.departure(to, arrival);
+        }
+        lateness
+    }
+
+    /// Leaving a city reached at `arrival`, after waiting and serving
+    #[inline]
+    fn departure(&self, city: usize, arrival: f64) -> f64 {
+        let window = &self.windows[city];
+        f64::max(arrival, window.ready) + window.service
+    }
+}
+
+/// Times along the tour of a replica by offset from the origin, offset 0
+/// being the origin and offset `n` the way back to it. A move only changes
+/// the times from its first changed position on, and once a departure is as
+/// before the rest of the schedule is too.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    /// Position of the origin in the path
+    origin_at: usize,
+    departures: Vec<f64>,
+    /// Lateness at each offset
+    late: Vec<f64>,
+    /// Total lateness of the tour
+    pub lateness: f64,
+    /// Departures and lateness of the last proposal from `pending_from` on,
+    /// kept by `commit`
+    pending: Vec<(f64, f64)>,
+    pending_from: usize,
+    pending_origin_at: usize,
+    pending_lateness: f64,
+}
+
+impl Schedule {
+    pub fn new<C: Cost>(
+        windows: &TimeWindows,
+        dmatrix: &DistanceMatrix<C>,
+        solution: &Solution,
+    ) -> Self {
+        let n = solution.size;
+        let mut schedule = Schedule {
+            origin_at: 0,
+            departures: vec![0.0; n + 1],
+            late: vec![0.0; n + 1],
+            lateness: 0.0,
+            pending: Vec::with_capacity(n + 1),
+            pending_from: 0,
+            pending_origin_at: 0,
+            pending_lateness: 0.0,
+        };
+        schedule.recompute(windows, dmatrix, solution);
+        schedule.commit();
+        schedule
+    }
+
+    /// Total lateness of the tour after the move was applied, the new times
+    /// are kept until the next proposal and take effect with `commit`
+    pub fn propose<C: Cost>(
+        &mut self,
+        windows: &TimeWindows,
+        dmatrix: &DistanceMatrix<C>,
+        proposal: &Move,
+        solution: &Solution,
+    ) -> f64 {
+        let n = solution.size;
+        let changed = match *proposal {
+            Move::Swap {
+                first,
+                second,
+                length,
+            } => {
+                let (low, high) = (usize::min(first, second), usize::max(first, second));
+                let (a, b) = solution.find_swap_indices(low, high, length);
+                [(a, length), (b, length)]
+            }
+            Move::Shuffle { start, length } => [(start, usize::min(length, n)); 2],
+            Move::Choose { position, .. } => [(position, 1); 2],
+        };
+        if changed.iter().all(|&(_, length)| length == 0) {
+            self.pending.clear();
+            self.pending_origin_at = self.origin_at;
+            self.pending_lateness = self.lateness;
+            return self.lateness;
+        }
+        let offset = |position: usize| (position + n - self.origin_at) % n;
+        // A window over the origin may have moved it, the tour is timed anew
+        if changed
+            .iter()
+            .any(|&(start, length)| offset(start) == 0 || offset(start) + length > n)
+        {
+            return self.recompute(windows, dmatrix, solution);
+        }
+        let first = changed
+            .iter()
+            .map(|&(start, _)| offset(start))
+            .min()
+            .unwrap();
+        let last = changed
+            .iter()
+            .map(|&(start, length)| offset(start) + length - 1)
+            .max()
+            .unwrap();
+        self.retime(windows, dmatrix, solution, first, last)
+    }
+
+    /// Keeps the times of the last proposal
+    pub fn commit(&mut self) {
+        for (k, &(departure, late)) in self.pending.iter().enumerate() {
+            self.departures[self.pending_from + k] = departure;
+            self.late[self.pending_from + k] = late;
+        }
+        self.pending.clear();
+        self.origin_at = self.pending_origin_at;
+        self.lateness = self.pending_lateness;
+    }
+
+    fn recompute<C: Cost>(
+        &mut self,
+        windows: &TimeWindows,
+        dmatrix: &DistanceMatrix<C>,
+        solution: &Solution,
+    ) -> f64 {
+        let n = solution.size;
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == windows.origin)
+            .unwrap();
+        let mut time = windows.departure(windows.origin, 0.0);
+        let mut lateness = 0.0;
+        self.pending.clear();
+        self.pending.push((time, 0.0));
+        for k in 1..=n {
+            let (from, to) = (solution.path[(at + k - 1) % n], solution.path[(at + k) % n]);
+            let arrival = time + dmatrix.cost(from, to).to_f64();
+            let late = f64::max(arrival - windows.windows[to].due, 0.0);
+            time = windows.departure(to, arrival);
+            lateness += late;
+            self.pending.push((time, late));
+        }
+        self.pending_from = 0;
+        self.pending_origin_at = at;
+        self.pending_lateness = lateness;
+        lateness
+    }
+
+    /// Times from offset `first` on, until they are the same as before past
+    /// the `last` changed offset
+    fn retime<C: Cost>(
+        &mut self,
+        windows: &TimeWindows,
+        dmatrix: &DistanceMatrix<C>,
+        solution: &Solution,
+        first: usize,
+        last: usize,
+    ) -> f64 {
+        let n = solution.size;
+        let at = self.origin_at;
+        let mut time = self.departures[first - 1];
+        let mut change = 0.0;
+        self.pending.clear();
+        for k in first..=n {
+            let (from, to) = (solution.path[(at + k - 1) % n], solution.path[(at + k) % n]);
+            let arrival = time + dmatrix.cost(from, to).to_f64();
+            let late = f64::max(arrival - windows.windows[to].due, 0.0);
+            time = windows.departure(to, arrival);
+            change += late - self.late[k];
+            self.pending.push((time, late));
+            if k > last && time == self.departures[k] {
+                break;
+            }
+        }
+        self.pending_from = first;
+        self.pending_origin_at = at;
+        self.pending_lateness = f64::max(self.lateness + change, 0.0);
+        self.pending_lateness
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn matrix(size: usize) -> DistanceMatrix<i64> {
+        DistanceMatrix::modular(size, [13, 7, 1, 17])
+    }
+
+    #[test]
+    fn test_waits_for_ready_and_counts_lateness() {
+        let dmatrix = DistanceMatrix::new(vec![vec![0, 2, 5], vec![5, 0, 3], vec![4, 5, 0]]);
+        let window = |ready, due, service| TimeWindow {
+            ready,
+            due,
+            service,
+        };
+        let windows = TimeWindows::new(vec![
+            window(0.0, 20.0, 0.0),
+            window(4.0, 10.0, 1.0),
+            window(0.0, 6.0, 2.0),
+        ]);
+        let solution = Solution::new(vec![0, 1, 2]);
+        // At 1 by 2, waits until 4 and leaves at 5; at 2 by 8, 2 late;
+        // back at 0 by 14
+        assert_eq!(windows.lateness(&dmatrix, &solution), vec![0.0, 0.0, 2.0]);
+        let schedule = Schedule::new(&windows, &dmatrix, &solution);
+        assert_eq!(schedule.lateness, 2.0);
+        assert!(windows.validate(3).is_ok());
+        assert!(TimeWindows::new(vec![window(5.0, 4.0, 0.0); 3])
+            .validate(3)
+            .is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_incremental_matches_full_schedule(
+            moves in prop::collection::vec(
+                (any::<bool>(), 0usize..16, 0usize..16, 0usize..=8, any::<bool>()),
+                1..20,
+            ),
+            dues in prop::collection::vec(10.0f64..120.0, 15),
+        ) {
+            let size = 16;
+            let dmatrix = matrix(size);
+            let mut windows: Vec<TimeWindow> = dues
+                .into_iter()
+                .map(|due| TimeWindow { ready: due / 2.0, due, service: 1.0 })
+                .collect();
+            windows.push(TimeWindow::OPEN);
+            // A closed path, the last city is the dummy origin
+            let windows = TimeWindows { windows, origin: size - 1 };
+            let mut solution = Solution::new((0..size).collect());
+            let mut schedule = Schedule::new(&windows, &dmatrix, &solution);
+            let mut backup = vec![];
+            for (shuffle, first, second, length, accept) in moves {
+                let proposal = if shuffle {
+                    Move::Shuffle { start: first, length: 2 * length }
+                } else {
+                    Move::Swap { first, second, length }
+                };
+                proposal.apply(&mut solution, &mut backup);
+                let lateness = schedule.propose(&windows, &dmatrix, &proposal, &solution);
+                let full: f64 = windows.lateness(&dmatrix, &solution).iter().sum();
+                prop_assert!((lateness - full).abs() < 1e-6);
+                if accept {
+                    schedule.commit();
+                } else {
+                    proposal.undo(&mut solution, &backup);
+                }
+                let full: f64 = windows.lateness(&dmatrix, &solution).iter().sum();
+                prop_assert!((schedule.lateness - full).abs() < 1e-6);
+            }
+        }
+    }
+}
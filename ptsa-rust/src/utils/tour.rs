@@ -0,0 +1,294 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cost::Cost, matrix::DistanceMatrix, solution::Solution, time_window::TimeWindows,
+    validation::ValidationError,
+};
+
+/// What a tour is: a cycle, or a Hamiltonian path with optionally fixed ends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TourKind {
+    /// Back to the first city at the end, the classic (A)TSP
+    #[default]
+    Cycle,
+    /// Every city once without returning, leaving `start` and entering `end`
+    /// when they are set
+    Path {
+        start: Option<usize>,
+        end: Option<usize>,
+    },
+}
+
+impl TourKind {
+    /// A path when it is asked for or has a fixed end, a cycle otherwise
+    pub fn new(open: bool, start: Option<usize>, end: Option<usize>) -> Self {
+        if open || start.is_some() || end.is_some() {
+            TourKind::Path { start, end }
+        } else {
+            TourKind::Cycle
+        }
+    }
+
+    pub fn is_path(&self) -> bool {
+        matches!(self, TourKind::Path { .. })
+    }
+
+    /// The city tours start at, the fixed start of a path
+    pub fn depot(&self) -> Option<usize> {
+        match *self {
+            TourKind::Cycle => None,
+            TourKind::Path { start, .. } => start,
+        }
+    }
+
+    pub fn validate(&self, size: usize) -> Result<(), ValidationError> {
+        let TourKind::Path { start, end } = *self else {
+            return Ok(());
+        };
+        for (name, city) in [("start", start), ("end", end)] {
+            if let Some(city) = city.filter(|&city| city >= size) {
+                return Err(ValidationError::Matrix(format!(
+                    "the path {} {} is out of the {} cities",
+                    name, city, size
+                )));
+            }
+        }
+        if let Some(city) = start.filter(|&start| Some(start) == end) {
+            return Err(ValidationError::Matrix(format!(
+                "the path cannot start and end at the same city {}",
+                city
+            )));
+        }
+        Ok(())
+    }
+
+    /// The cycle instance of a path: a dummy city, the last one, goes from
+    /// the end of the path back to its start. Arcs that would put another
+    /// city at a fixed end cost more than any tour.
+    pub fn closed<C: Cost>(&self, dmatrix: &DistanceMatrix<C>) -> DistanceMatrix<C> {
+        let TourKind::Path { start, end } = *self else {
+            return dmatrix.clone();
+        };
+        let n = dmatrix.size;
+        let forbidden = forbidden_cost(dmatrix).expect("validated to fit the cost type");
+        let free = |city: usize, fixed: Option<usize>| fixed.is_none_or(|fixed| fixed == city);
+        let mut data = Vec::with_capacity((n + 1) * (n + 1));
+        for from in 0..n {
+            data.extend_from_slice(dmatrix.row(from));
+            data.push(if free(from, end) { C::ZERO } else { forbidden });
+        }
+        data.extend((0..n).map(|to| if free(to, start) { C::ZERO } else { forbidden }));
+        data.push(C::ZERO);
+        // The order of a sequential ordering problem and the schedule of
+        // time windows start after the dummy city
+        DistanceMatrix {
+            precedences: dmatrix.precedences.clone(),
+            time_windows: dmatrix.time_windows.as_ref().map(TimeWindows::closed),
+            ..DistanceMatrix::from_flat(data, n + 1)
+        }
+    }
+
+    /// Moves the fixed ends of a tour of the `closed` instance next to the
+    /// dummy city, so that the tour costs no forbidden arc
+    pub fn repair(&self, solution: &mut Solution) {
+        let TourKind::Path { start, end } = *self else {
+            return;
+        };
+        let dummy = solution.size - 1;
+        let path = &mut solution.path;
+        path.retain(|&city| Some(city) != start && Some(city) != end && city != dummy);
+        if let Some(end) = end {
+            path.push(end);
+        }
+        path.push(dummy);
+        if let Some(start) = start {
+            path.push(start);
+        }
+    }
+
+    /// Whether a tour of the `closed` instance has the fixed ends in place
+    pub fn holds(&self, solution: &Solution) -> bool {
+        let TourKind::Path { start, end } = *self else {
+            return true;
+        };
+        let n = solution.size;
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == n - 1)
+            .unwrap();
+        start.is_none_or(|start| solution.path[(at + 1) % n] == start)
+            && end.is_none_or(|end| solution.path[(at + n - 1) % n] == end)
+    }
+
+    /// The tour of the original instance from one of the `closed` instance,
+    /// starting at the depot: after the dummy city for a path
+    pub fn opened(&self, solution: &Solution) -> Solution {
+        if !self.is_path() {
+            return solution.clone();
+        }
+        let dummy = solution.size - 1;
+        let at = solution
+            .path
+            .iter()
+            .position(|&city| city == dummy)
+            .unwrap();
+        let path = solution.path[at + 1..]
+            .iter()
+            .chain(solution.path[..at].iter())
+            .copied()
+            .collect();
+        Solution::new(path)
+    }
+}
+
+impl fmt::Display for TourKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let city = |city: Option<usize>| city.map_or("any".to_string(), |city| city.to_string());
+        match *self {
+            TourKind::Cycle => write!(f, "cycle"),
+            TourKind::Path { start, end } => {
+                write!(f, "path from {} to {}", city(start), city(end))
+            }
+        }
+    }
+}
+
+/// Tours of the `closed` instance cross at most two forbidden arcs, and a
+/// move compares a few more; this many of them have to fit the cost type
+const FORBIDDEN_HEADROOM: usize = 8;
+
+/// More than the costliest tour: the sum of the largest arc out of every
+/// city. `None` when the cost type cannot hold `FORBIDDEN_HEADROOM` of them.
+pub(crate) fn forbidden_cost<C: Cost>(dmatrix: &DistanceMatrix<C>) -> Option<C> {
+    let forbidden = (0..dmatrix.size)
+        .map(|from| {
+            dmatrix
+                .row(from)
+                .iter()
+                .enumerate()
+                .filter(|&(to, _)| to != from)
+                .map(|(_, &cost)| cost)
+                .fold(
+                    C::ZERO,
+                    |largest, cost| if cost > largest { cost } else { largest },
+                )
+        })
+        .try_fold(C::from_f64(1.0), C::checked_add)?;
+    (1..FORBIDDEN_HEADROOM).try_fold(forbidden, |total, _| total.checked_add(forbidden))?;
+    Some(forbidden)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::exact::solve_exact;
+
+    fn matrix() -> DistanceMatrix<i64> {
+        DistanceMatrix::modular(7, [5, 3, 1, 13])
+    }
+
+    /// Cheapest path over every permutation
+    fn brute_force(dmatrix: &DistanceMatrix<i64>, kind: TourKind) -> i64 {
+        fn permutations(rest: Vec<usize>, path: &mut Vec<usize>, visit: &mut impl FnMut(&[usize])) {
+            if rest.is_empty() {
+                visit(path);
+            }
+            for (i, &city) in rest.iter().enumerate() {
+                let mut left = rest.clone();
+                left.remove(i);
+                path.push(city);
+                permutations(left, path, visit);
+                path.pop();
+            }
+        }
+        let TourKind::Path { start, end } = kind else {
+            unreachable!()
+        };
+        let mut best = i64::MAX;
+        permutations((0..dmatrix.size).collect(), &mut vec![], &mut |path| {
+            if start.is_some_and(|start| path[0] != start)
+                || end.is_some_and(|end| path[path.len() - 1] != end)
+            {
+                return;
+            }
+            let cost = path
+                .windows(2)
+                .map(|arc| dmatrix.cost(arc[0], arc[1]))
+                .sum();
+            best = best.min(cost);
+        });
+        best
+    }
+
+    #[test]
+    fn test_closed_instance_solves_paths() {
+        let dmatrix = matrix();
+        for kind in [
+            TourKind::new(true, None, None),
+            TourKind::new(false, Some(2), None),
+            TourKind::new(false, None, Some(4)),
+            TourKind::new(false, Some(3), Some(0)),
+        ] {
+            let closed = kind.closed(&dmatrix);
+            let best = solve_exact(&closed, None).unwrap();
+            let tour = kind.opened(&best.solution);
+            let original = dmatrix.clone().with_kind(kind);
+            assert_eq!(tour.cost(&original), best.cost);
+            assert_eq!(best.cost, brute_force(&dmatrix, kind));
+            if let Some(depot) = kind.depot() {
+                assert_eq!(tour.path[0], depot);
+            }
+        }
+    }
+
+    #[test]
+    fn test_repair_avoids_forbidden_arcs() {
+        let dmatrix = matrix();
+        let kind = TourKind::new(false, Some(1), Some(5));
+        let closed = kind.closed(&dmatrix);
+        let mut solution = Solution::new(vec![7, 0, 1, 2, 3, 4, 5, 6]);
+        assert!(!kind.holds(&solution));
+        kind.repair(&mut solution);
+        assert!(kind.holds(&solution));
+        let tour = kind.opened(&solution);
+        assert_eq!(tour.path[0], 1);
+        assert_eq!(tour.path[6], 5);
+        assert_eq!(solution.cost(&closed), tour.cost(&dmatrix.with_kind(kind)));
+    }
+
+    #[test]
+    fn test_forbidden_cost_fits_the_cost_type() {
+        let scaled = |scale: i32| {
+            DistanceMatrix::new(
+                (0..4)
+                    .map(|i| {
+                        (0..4)
+                            .map(|j| if i == j { 0 } else { scale - i - j })
+                            .collect()
+                    })
+                    .collect::<Vec<Vec<i32>>>(),
+            )
+        };
+        let path = TourKind::new(false, Some(0), None);
+        let small = scaled(1000).with_kind(path);
+        assert_eq!(forbidden_cost(&small), Some(4 * 1000 - 1 - 1 - 2 - 3 + 1));
+        assert!(small.validate().is_ok());
+        let large = scaled(i32::MAX / 4).with_kind(path);
+        assert_eq!(forbidden_cost(&large), None);
+        assert!(matches!(large.validate(), Err(ValidationError::Matrix(_))));
+        assert!(large.with_kind(TourKind::Cycle).validate().is_err());
+        let wide = DistanceMatrix::new(vec![vec![i64::from(i32::MAX) / 4; 4]; 4]);
+        assert!(forbidden_cost(&wide).is_some());
+    }
+
+    #[test]
+    fn test_rejects_bad_ends() {
+        assert!(TourKind::new(false, Some(7), None).validate(7).is_err());
+        assert!(TourKind::new(false, Some(2), Some(2)).validate(7).is_err());
+        assert!(TourKind::new(false, Some(2), Some(3)).validate(7).is_ok());
+    }
+}
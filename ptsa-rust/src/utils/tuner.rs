@@ -0,0 +1,645 @@
+use std::{fmt, fs, path::Path, str::FromStr, time::Duration};
+
+use rand::Rng;
+use serde_json::Value;
+
+use super::{
+    config::{parse_table, ConfigError, ConfigFormat},
+    cost::Cost,
+    helpers::parallel_map,
+    instance::KnownInstance,
+    params::Params,
+    random::{reseed, rng},
+    solver::{Solver, Stop},
+    validation::ValidationError,
+};
+
+/// Race blocks evaluated before the first elimination test
+const FIRST_TEST: usize = 5;
+/// Attempts at drawing a valid configuration before giving up on the space
+const SAMPLING_ATTEMPTS: usize = 1000;
+
+/// Values a tuned `Params` field is drawn from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Domain {
+    /// Uniform whole number between the bounds, both included
+    Integer { min: i64, max: i64 },
+    /// Uniform number between the bounds
+    Real { min: f64, max: f64 },
+    /// One of the values, written as in a config file
+    Choice(Vec<Value>),
+}
+
+impl Domain {
+    fn sample(&self, rng: &mut impl Rng) -> Value {
+        match self {
+            Domain::Integer { min, max } => Value::from(rng.gen_range(*min..=*max)),
+            Domain::Real { min, max } => Value::from(rng.gen_range(*min..=*max)),
+            Domain::Choice(values) => values[rng.gen_range(0..values.len())].clone(),
+        }
+    }
+}
+
+/// Tuned `Params` fields, the others keep their base value.
+///
+/// Read from a table of `field = { min = .., max = .. }` ranges, whole numbers
+/// when both bounds are, and `field = [..]` lists of choices:
+///
+/// ```toml
+/// number_of_states = { min = 10, max = 60 }
+/// cooling_rate = { min = 0.8, max = 0.99 }
+/// neighbour_ranking = ["cost", "alpha"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchSpace {
+    pub fields: Vec<(String, Domain)>,
+}
+
+impl SearchSpace {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        SearchSpace::parse(&fs::read_to_string(path)?, ConfigFormat::of(path))
+    }
+
+    pub fn parse(text: &str, format: ConfigFormat) -> Result<Self, ConfigError> {
+        let known = serde_json::to_value(Params::default()).expect("Params always serialize");
+        let mut fields = vec![];
+        for (name, value) in parse_table(text, format)? {
+            if known.get(&name).is_none() {
+                return Err(ConfigError::Parse(format!("unknown parameter {:?}", name)));
+            }
+            let domain = match &value {
+                Value::Array(values) if !values.is_empty() => Domain::Choice(values.clone()),
+                Value::Object(range) => match (range.get("min"), range.get("max")) {
+                    (Some(min), Some(max)) if min.is_i64() && max.is_i64() => {
+                        let (min, max) = (min.as_i64().unwrap(), max.as_i64().unwrap());
+                        (min <= max).then_some(Domain::Integer { min, max })
+                    }
+                    (Some(min), Some(max)) => match (min.as_f64(), max.as_f64()) {
+                        (Some(min), Some(max)) if min <= max => Some(Domain::Real { min, max }),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+                .ok_or_else(|| ConfigError::Parse(format!("{} needs numbers min <= max", name)))?,
+                _ => {
+                    return Err(ConfigError::Parse(format!(
+                        "{} has to be a {{ min, max }} range or a list of choices, got {}",
+                        name, value
+                    )))
+                }
+            };
+            fields.push((name, domain));
+        }
+        Ok(SearchSpace { fields })
+    }
+
+    /// Base parameters with every field of the space drawn at random,
+    /// redrawn until they are valid together
+    pub fn sample(&self, base: &Params, rng: &mut impl Rng) -> Result<Params, ValidationError> {
+        let base = serde_json::to_value(base).expect("Params always serialize");
+        for _ in 0..SAMPLING_ATTEMPTS {
+            let mut fields = base.clone();
+            for (name, domain) in self.fields.iter() {
+                fields[name] = domain.sample(rng);
+            }
+            if let Ok(params) = serde_json::from_value::<Params>(fields) {
+                if params.validate().is_ok() {
+                    return Ok(params);
+                }
+            }
+        }
+        Err(ValidationError::Parameters(
+            "the search space yields no valid parameters".to_string(),
+        ))
+    }
+}
+
+/// How the candidates are narrowed down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// F-race: every candidate runs on each new instance, the ones the
+    /// Friedman test finds worse than the best are dropped
+    Race,
+    /// Rounds with an equal share of the budget, the worse half dropped
+    /// after each
+    SuccessiveHalving,
+}
+
+impl FromStr for Method {
+    type Err = ValidationError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "race" => Ok(Method::Race),
+            "halving" => Ok(Method::SuccessiveHalving),
+            _ => Err(ValidationError::Parameters(format!(
+                "unknown tuning method {:?}, expected race or halving",
+                name
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Race => write!(f, "race"),
+            Method::SuccessiveHalving => write!(f, "halving"),
+        }
+    }
+}
+
+/// A configuration with its deficits, in percent over the optimum,
+/// one per evaluated task
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub id: usize,
+    pub params: Params,
+    pub deficits: Vec<f64>,
+    /// Round (race block or halving rung) after which it was dropped,
+    /// `None` for the survivors
+    pub eliminated: Option<usize>,
+}
+
+impl Candidate {
+    pub fn mean_deficit(&self) -> f64 {
+        self.deficits.iter().sum::<f64>() / self.deficits.len().max(1) as f64
+    }
+}
+
+/// Evaluated candidates, ranked best first
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    pub candidates: Vec<Candidate>,
+    /// Tuned fields, the columns of the CSV
+    pub fields: Vec<String>,
+    /// Solver runs spent
+    pub runs: usize,
+}
+
+impl Tuning {
+    pub fn best(&self) -> &Candidate {
+        &self.candidates[0]
+    }
+
+    /// One row per candidate with its rank, statistics and tuned fields
+    pub fn to_csv(&self) -> String {
+        let mut text = String::from("rank,id,evaluations,mean_deficit,eliminated");
+        for field in self.fields.iter() {
+            text.push(',');
+            text.push_str(field);
+        }
+        text.push('\n');
+        for (rank, candidate) in self.candidates.iter().enumerate() {
+            let eliminated = candidate
+                .eliminated
+                .map(|round| round.to_string())
+                .unwrap_or_default();
+            text.push_str(&format!(
+                "{},{},{},{},{}",
+                rank + 1,
+                candidate.id,
+                candidate.deficits.len(),
+                candidate.mean_deficit(),
+                eliminated
+            ));
+            let params = serde_json::to_value(&candidate.params).expect("Params always serialize");
+            for field in self.fields.iter() {
+                let value = match &params[field] {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                text.push(',');
+                text.push_str(&csv_field(&value));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Searches the parameters with the lowest deficit on instances with known
+/// optima, running `budget` solver runs at most, `jobs` of them at a time.
+///
+/// Task `t` of the evaluation is a run on instance `t % instances`, every
+/// candidate of a round runs on the same tasks. Runs stop early at the optimum.
+/// With a seed, the candidates are drawn from it and task `t` is seeded with
+/// `seed + t` for every candidate.
+#[derive(Debug, Clone)]
+pub struct Tuner {
+    pub space: SearchSpace,
+    /// Parameters the space is drawn over, also the first candidate
+    pub base: Params,
+    /// Limits of each run
+    pub stop: Stop,
+    pub method: Method,
+    /// Configurations drawn at the start
+    pub candidates: usize,
+    pub budget: usize,
+    pub jobs: usize,
+    pub seed: Option<u64>,
+    /// Print the progress of the rounds
+    pub verbose: bool,
+}
+
+impl Default for Tuner {
+    fn default() -> Self {
+        Tuner {
+            space: SearchSpace::default(),
+            base: Params::default(),
+            stop: Stop::after(Duration::from_secs(10)),
+            method: Method::Race,
+            candidates: 16,
+            budget: 200,
+            jobs: 1,
+            seed: None,
+            verbose: false,
+        }
+    }
+}
+
+impl Tuner {
+    pub fn tune(&self, instances: &[KnownInstance]) -> Result<Tuning, ValidationError> {
+        self.validate(instances)?;
+        if let Some(seed) = self.seed {
+            reseed(seed);
+        }
+        let rng = &mut rng();
+        let mut candidates = vec![Candidate {
+            id: 0,
+            params: self.base.clone(),
+            deficits: vec![],
+            eliminated: None,
+        }];
+        for id in 1..self.candidates {
+            candidates.push(Candidate {
+                id,
+                params: self.space.sample(&self.base, rng)?,
+                deficits: vec![],
+                eliminated: None,
+            });
+        }
+
+        let runs = match self.method {
+            Method::Race => self.race(&mut candidates, instances)?,
+            Method::SuccessiveHalving => self.halve(&mut candidates, instances)?,
+        };
+        candidates.sort_by(|a, b| {
+            b.deficits
+                .len()
+                .cmp(&a.deficits.len())
+                .then(a.mean_deficit().total_cmp(&b.mean_deficit()))
+        });
+        Ok(Tuning {
+            candidates,
+            fields: self
+                .space
+                .fields
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            runs,
+        })
+    }
+
+    fn validate(&self, instances: &[KnownInstance]) -> Result<(), ValidationError> {
+        let invalid = |message: &str| Err(ValidationError::Parameters(message.to_string()));
+        if instances.is_empty() {
+            return invalid("tuning needs at least one instance");
+        }
+        if let Some(known) = instances.iter().find(|known| known.optimum <= 0.0) {
+            return Err(ValidationError::Parameters(format!(
+                "optimum of {} has to be positive, got {}",
+                known.instance.name, known.optimum
+            )));
+        }
+        if self.candidates == 0 || self.jobs == 0 {
+            return invalid("tuning needs at least one candidate and one job");
+        }
+        if self.budget < self.candidates {
+            return invalid("the budget has to cover one run of every candidate");
+        }
+        self.base.validate()?;
+        Solver::builder().stop(self.stop).build().map(|_| ())
+    }
+
+    /// F-race, returns the runs spent
+    fn race(
+        &self,
+        candidates: &mut [Candidate],
+        instances: &[KnownInstance],
+    ) -> Result<usize, ValidationError> {
+        let mut runs = 0;
+        for block in 0.. {
+            let alive = alive(candidates);
+            if alive.len() <= 1 || runs + alive.len() > self.budget {
+                break;
+            }
+            let work: Vec<(usize, usize)> = alive.iter().map(|&c| (c, block)).collect();
+            for (&(c, _), deficit) in work
+                .iter()
+                .zip(self.evaluate(candidates, &work, instances)?)
+            {
+                candidates[c].deficits.push(deficit);
+            }
+            runs += work.len();
+
+            if block + 1 >= FIRST_TEST {
+                let deficits: Vec<&[f64]> =
+                    alive.iter().map(|&c| &candidates[c].deficits[..]).collect();
+                for loser in friedman_losers(&deficits) {
+                    candidates[alive[loser]].eliminated = Some(block + 1);
+                }
+            }
+            self.report(block + 1, candidates);
+        }
+        Ok(runs)
+    }
+
+    /// Successive halving, returns the runs spent
+    fn halve(
+        &self,
+        candidates: &mut [Candidate],
+        instances: &[KnownInstance],
+    ) -> Result<usize, ValidationError> {
+        let rungs = (candidates.len() as f64).log2().ceil().max(1.0) as usize;
+        let (mut runs, mut task) = (0, 0);
+        for rung in 1..=rungs {
+            let mut alive = alive(candidates);
+            let share = usize::max(self.budget / rungs / alive.len(), 1);
+            let share = usize::min(share, (self.budget - runs) / alive.len());
+            if alive.len() <= 1 || share == 0 {
+                break;
+            }
+            let work: Vec<(usize, usize)> = alive
+                .iter()
+                .flat_map(|&c| (task..task + share).map(move |t| (c, t)))
+                .collect();
+            for (&(c, _), deficit) in work
+                .iter()
+                .zip(self.evaluate(candidates, &work, instances)?)
+            {
+                candidates[c].deficits.push(deficit);
+            }
+            runs += work.len();
+            task += share;
+
+            alive.sort_by(|&a, &b| {
+                candidates[a]
+                    .mean_deficit()
+                    .total_cmp(&candidates[b].mean_deficit())
+            });
+            for &c in alive[alive.len().div_ceil(2)..].iter() {
+                candidates[c].eliminated = Some(rung);
+            }
+            self.report(rung, candidates);
+        }
+        Ok(runs)
+    }
+
+    fn report(&self, round: usize, candidates: &[Candidate]) {
+        if !self.verbose {
+            return;
+        }
+        let alive = alive(candidates);
+        let best = alive
+            .iter()
+            .map(|&c| &candidates[c])
+            .min_by(|a, b| a.mean_deficit().total_cmp(&b.mean_deficit()))
+            .unwrap();
+        println!(
+            "Round {}: {} candidates left, best #{} with {:.3}% mean deficit",
+            round,
+            alive.len(),
+            best.id,
+            best.mean_deficit()
+        );
+    }
+
+    /// Deficits of the `(candidate, task)` runs, in order
+    fn evaluate(
+        &self,
+        candidates: &[Candidate],
+        work: &[(usize, usize)],
+        instances: &[KnownInstance],
+    ) -> Result<Vec<f64>, ValidationError> {
+        parallel_map(self.jobs, work.len(), |i| {
+            let (c, task) = work[i];
+            self.run(
+                &candidates[c].params,
+                &instances[task % instances.len()],
+                task,
+            )
+        })
+        .into_iter()
+        .collect()
+    }
+
+    fn run(
+        &self,
+        params: &Params,
+        known: &KnownInstance,
+        task: usize,
+    ) -> Result<f64, ValidationError> {
+        let mut builder = Solver::builder()
+            .params(params.clone())
+            .stop(self.stop.or_at_cost(known.optimum));
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed.wrapping_add(task as u64));
+        }
+        let solver = builder.build()?;
+        let cost = if known.instance.is_integral() {
+            solver
+                .solve(&known.instance.matrix::<i64>())?
+                .best
+                .cost
+                .to_f64()
+        } else {
+            solver.solve(&known.instance.matrix::<f64>())?.best.cost
+        };
+        Ok(known.deficit(cost))
+    }
+}
+
+fn alive(candidates: &[Candidate]) -> Vec<usize> {
+    (0..candidates.len())
+        .filter(|&c| candidates[c].eliminated.is_none())
+        .collect()
+}
+
+/// Candidates worse than the best one by the Friedman test with Conover's
+/// post-hoc comparison at the 95% level, `deficits[candidate][block]`
+fn friedman_losers(deficits: &[&[f64]]) -> Vec<usize> {
+    let k = deficits.len();
+    let blocks = deficits.first().map_or(0, |row| row.len());
+    if k < 2 || blocks < 2 {
+        return vec![];
+    }
+    // Ranks within every block, ties share their mean rank
+    let mut ranks = vec![vec![0.0; blocks]; k];
+    for block in 0..blocks {
+        let mut order: Vec<usize> = (0..k).collect();
+        order.sort_by(|&a, &b| deficits[a][block].total_cmp(&deficits[b][block]));
+        let mut start = 0;
+        while start < k {
+            let value = deficits[order[start]][block];
+            let end = (start..k)
+                .find(|&i| deficits[order[i]][block] != value)
+                .unwrap_or(k);
+            let rank = (start + end + 1) as f64 / 2.0;
+            for &c in order[start..end].iter() {
+                ranks[c][block] = rank;
+            }
+            start = end;
+        }
+    }
+
+    let (kf, b) = (k as f64, blocks as f64);
+    let sums: Vec<f64> = ranks.iter().map(|row| row.iter().sum()).collect();
+    let squares: f64 = ranks.iter().flatten().map(|rank| rank * rank).sum();
+    let correction = b * kf * (kf + 1.0).powi(2) / 4.0;
+    let spread = squares - correction;
+    if spread <= 0.0 {
+        return vec![];
+    }
+    let statistic = (kf - 1.0)
+        * sums
+            .iter()
+            .map(|sum| (sum - b * (kf + 1.0) / 2.0).powi(2))
+            .sum::<f64>()
+        / spread;
+    if statistic <= chi_squared_quantile(kf - 1.0) {
+        return vec![];
+    }
+    let freedom = (b - 1.0) * (kf - 1.0);
+    let critical = t_quantile(freedom)
+        * (2.0 * b * spread * (1.0 - statistic / (b * (kf - 1.0))).max(0.0) / freedom).sqrt();
+    let best = sums.iter().copied().fold(f64::INFINITY, f64::min);
+    (0..k).filter(|&c| sums[c] - best > critical).collect()
+}
+
+/// 95th percentile of the chi-squared distribution, Wilson-Hilferty
+/// approximation above one degree of freedom
+fn chi_squared_quantile(freedom: f64) -> f64 {
+    if freedom <= 1.0 {
+        return 1.959_964_f64.powi(2);
+    }
+    let z = 1.644_853_6;
+    let h = 2.0 / (9.0 * freedom);
+    freedom * (1.0 - h + z * h.sqrt()).powi(3)
+}
+
+/// 97.5th percentile of Student's t distribution, Cornish-Fisher expansion
+fn t_quantile(freedom: f64) -> f64 {
+    let z: f64 = 1.959_964;
+    z + (z.powi(3) + z) / (4.0 * freedom)
+        + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * freedom.powi(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{
+        exact::held_karp, instance::Instance, matrix::DistanceMatrix, tour::TourKind,
+    };
+
+    #[test]
+    fn test_quantiles() {
+        assert!((chi_squared_quantile(1.0) - 3.841).abs() < 0.05);
+        assert!((chi_squared_quantile(4.0) - 9.488).abs() < 0.05);
+        assert!((t_quantile(10.0) - 2.228).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_friedman_drops_the_worst() {
+        let good = [1.0, 0.5, 0.8, 1.2, 0.9, 0.7];
+        let middle = [0.9, 0.6, 0.7, 1.1, 1.0, 0.6];
+        let bad = [5.0, 4.5, 6.0, 5.5, 4.8, 5.2];
+        assert_eq!(friedman_losers(&[&good, &middle, &bad]), vec![2]);
+        assert!(friedman_losers(&[&good, &good]).is_empty());
+    }
+
+    #[test]
+    fn test_search_space() {
+        let text =
+            "number_of_states = { min = 10, max = 60 }\ncooling_rate = { min = 0.8, max = 0.99 }\n\
+                    neighbour_ranking = [\"cost\", \"alpha\"]\n";
+        let space = SearchSpace::parse(text, ConfigFormat::Toml).unwrap();
+        assert!(space.fields.contains(&(
+            "number_of_states".to_string(),
+            Domain::Integer { min: 10, max: 60 }
+        )));
+        let params = space.sample(&Params::default(), &mut rng()).unwrap();
+        assert!((10..=60).contains(&params.number_of_states));
+        assert!((0.8..=0.99).contains(&params.cooling_rate));
+
+        let parse = |text: &str| SearchSpace::parse(text, ConfigFormat::Toml);
+        assert!(parse("cooling_rat = [0.9]").is_err());
+        assert!(parse("cooling_rate = { min = 0.99, max = 0.8 }").is_err());
+        assert!(parse("cooling_rate = 0.9").is_err());
+    }
+
+    #[test]
+    fn test_halving_ranks_every_candidate() {
+        let size = 16;
+        let weights = DistanceMatrix::<f64>::modular(size, [7, 13, 5, 29]).data;
+        let instance = Instance {
+            name: "small".to_string(),
+            size,
+            weights,
+            kind: TourKind::Cycle,
+            precedences: vec![],
+            clusters: vec![],
+        };
+        let optimum = held_karp(&instance.matrix::<f64>()).cost;
+        let instances = [KnownInstance { instance, optimum }];
+        // Random starts, no exact solver and no polish: only the annealing,
+        // and so the tuned field, decides the deficits. The initial
+        // temperatures are in [0, 1], `max_temperature` scales the segments.
+        let tuner = Tuner {
+            space: SearchSpace::parse(
+                "max_temperature = { min = 0.2, max = 5.0 }",
+                ConfigFormat::Toml,
+            )
+            .unwrap(),
+            base: Params {
+                number_of_repeats: 1,
+                number_of_states: 4,
+                probability_of_heuristic: 0.0,
+                exact_solver_max_size: 0,
+                polish_final: false,
+                ..Params::default()
+            },
+            stop: Stop::after_iterations(5),
+            method: Method::SuccessiveHalving,
+            candidates: 4,
+            budget: 16,
+            jobs: 2,
+            seed: Some(3),
+            verbose: false,
+        };
+        let tuning = tuner.tune(&instances).unwrap();
+        assert_eq!(tuning.candidates.len(), 4);
+        assert!(tuning.runs <= 16);
+        assert!(tuning.best().eliminated.is_none());
+        assert_eq!(tuning.to_csv().lines().count(), 5);
+        let first_rung: Vec<f64> = tuning
+            .candidates
+            .iter()
+            .map(|candidate| candidate.deficits[0])
+            .collect();
+        assert!(first_rung.iter().any(|&deficit| deficit != first_rung[0]));
+        // The same seed draws the same candidates and runs them alike
+        let again = tuner.tune(&instances).unwrap();
+        assert_eq!(again.to_csv(), tuning.to_csv());
+    }
+}
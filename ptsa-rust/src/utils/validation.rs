@@ -0,0 +1,381 @@
+use std::fmt;
+
+#[cfg(feature = "python")]
+use pyo3::{
+    create_exception,
+    exceptions::{PyOSError, PyValueError},
+    prelude::*,
+};
+
+#[cfg(feature = "python")]
+use super::instance::InstanceError;
+use super::{
+    cost::Cost,
+    matrix::DistanceMatrix,
+    params::Params,
+    precedence::Precedences,
+    tour::{forbidden_cost, TourKind},
+};
+
+/// Smallest instance the annealer can work on
+pub const MIN_DIMENSION: usize = 3;
+
+/// Arcs beyond a tour that the change of a move may add up
+const MOVE_HEADROOM: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    Matrix(String),
+    Parameters(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Matrix(message) => write!(f, "Invalid distance matrix: {}", message),
+            ValidationError::Parameters(message) => write!(f, "Invalid parameters: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[cfg(feature = "python")]
+create_exception!(
+    ptsa_rust,
+    PtsaError,
+    PyValueError,
+    "Base class of the errors raised by the solver."
+);
+#[cfg(feature = "python")]
+create_exception!(
+    ptsa_rust,
+    InvalidMatrixError,
+    PtsaError,
+    "The distance matrix is not square, too small or has NaN, negative or infinite entries."
+);
+#[cfg(feature = "python")]
+create_exception!(
+    ptsa_rust,
+    InvalidParametersError,
+    PtsaError,
+    "A parameter is out of its allowed range."
+);
+
+#[cfg(feature = "python")]
+impl From<ValidationError> for PyErr {
+    fn from(error: ValidationError) -> Self {
+        match error {
+            ValidationError::Matrix(_) => InvalidMatrixError::new_err(error.to_string()),
+            ValidationError::Parameters(_) => InvalidParametersError::new_err(error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<InstanceError> for PyErr {
+    fn from(error: InstanceError) -> Self {
+        match error {
+            InstanceError::Io(_) => PyOSError::new_err(error.to_string()),
+            InstanceError::Format(_) => InvalidMatrixError::new_err(error.to_string()),
+        }
+    }
+}
+
+impl<C: Cost> DistanceMatrix<C> {
+    /// Same as `new`, but reports a non-square input instead of panicking
+    pub fn try_new(matrix: Vec<Vec<C>>) -> Result<Self, ValidationError> {
+        let size = matrix.len();
+        if let Some((i, row)) = matrix.iter().enumerate().find(|(_, row)| row.len() != size) {
+            return Err(ValidationError::Matrix(format!(
+                "expected a square matrix of {} rows, row {} has {} entries",
+                size,
+                i,
+                row.len()
+            )));
+        }
+        Ok(DistanceMatrix::new(matrix))
+    }
+
+    /// Checks that the instance can be solved: at least three cities and
+    /// only finite, non-negative distances, small enough for tours to be
+    /// summed in the cost type. The diagonal is never travelled, so it is
+    /// not checked.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.size < MIN_DIMENSION {
+            return Err(ValidationError::Matrix(format!(
+                "at least {} cities are needed, got {}",
+                MIN_DIMENSION, self.size
+            )));
+        }
+        for from in 0..self.size {
+            for (to, &cost) in self.row(from).iter().enumerate() {
+                if from == to {
+                    continue;
+                }
+                if !cost.is_finite() {
+                    return Err(ValidationError::Matrix(format!(
+                        "entry [{}][{}] is {}",
+                        from, to, cost
+                    )));
+                }
+                if cost < C::ZERO {
+                    return Err(ValidationError::Matrix(format!(
+                        "entry [{}][{}] is negative ({})",
+                        from, to, cost
+                    )));
+                }
+            }
+        }
+        if !self.tours_fit() {
+            return Err(ValidationError::Matrix(format!(
+                "the distances are too large to sum a tour in {}",
+                std::any::type_name::<C>()
+            )));
+        }
+        self.kind.validate(self.size)?;
+        if self.kind.is_path() && forbidden_cost(self).is_none() {
+            return Err(ValidationError::Matrix(format!(
+                "the distances are too large to fix the ends of a path in {}",
+                std::any::type_name::<C>()
+            )));
+        }
+        if let Some(precedences) = &self.precedences {
+            self.validate_precedences(precedences)?;
+        }
+        if let Some(time_windows) = &self.time_windows {
+            time_windows.validate(self.size)?;
+        }
+        if let Some(fleet) = &self.fleet {
+            fleet.validate(self.size)?;
+            if self.kind.is_path() || self.precedences.is_some() || self.time_windows.is_some() {
+                return Err(ValidationError::Matrix(
+                    "a fleet drives cycles from the depot, without precedences or time windows"
+                        .to_string(),
+                ));
+            }
+        }
+        if let Some(clusters) = &self.clusters {
+            clusters.validate(self.size)?;
+            if self.kind.is_path()
+                || self.precedences.is_some()
+                || self.time_windows.is_some()
+                || self.fleet.is_some()
+            {
+                return Err(ValidationError::Matrix(
+                    "clusters are visited on cycles, without precedences, time windows or a fleet"
+                        .to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the costliest tour, with room for the few arcs more that the
+    /// change of a move sums, fits the cost type. The depot copies of a fleet
+    /// lengthen the tour.
+    fn tours_fit(&self) -> bool {
+        let largest = (0..self.size)
+            .flat_map(|from| {
+                self.row(from)
+                    .iter()
+                    .enumerate()
+                    .filter(move |&(to, _)| to != from)
+            })
+            .fold(
+                C::ZERO,
+                |largest, (_, &cost)| if cost > largest { cost } else { largest },
+            );
+        let vehicles = self.fleet.as_ref().map_or(0, |fleet| fleet.vehicles);
+        let arcs = self
+            .size
+            .saturating_add(vehicles)
+            .saturating_add(MOVE_HEADROOM);
+        (0..arcs)
+            .try_fold(C::ZERO, |total, _| total.checked_add(largest))
+            .is_some()
+    }
+
+    /// Precedences need a path whose fixed ends can be first and last
+    fn validate_precedences(&self, precedences: &Precedences) -> Result<(), ValidationError> {
+        precedences.validate(self.size)?;
+        let TourKind::Path { start, end } = self.kind else {
+            return Err(ValidationError::Matrix(
+                "precedences need a path, the tour kind is a cycle".to_string(),
+            ));
+        };
+        if let Some(start) = start.filter(|&start| !precedences.before[start].is_empty()) {
+            return Err(ValidationError::Matrix(format!(
+                "the path starts at city {}, which has predecessors",
+                start
+            )));
+        }
+        if let Some(end) = end.filter(|&end| {
+            precedences
+                .before
+                .iter()
+                .any(|before| before.contains(&end))
+        }) {
+            return Err(ValidationError::Matrix(format!(
+                "the path ends at city {}, which has to precede others",
+                end
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn check(condition: bool, message: impl FnOnce() -> String) -> Result<(), ValidationError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(ValidationError::Parameters(message()))
+    }
+}
+
+fn check_probability(name: &str, value: f64) -> Result<(), ValidationError> {
+    check((0.0..=1.0).contains(&value), || {
+        format!("{} has to be in [0, 1], got {}", name, value)
+    })
+}
+
+impl Params {
+    /// Range checks that do not depend on the instance
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        check(self.number_of_states >= 2, || {
+            format!(
+                "number_of_states has to be at least 2, got {}",
+                self.number_of_states
+            )
+        })?;
+        check(self.number_of_repeats >= 1, || {
+            "number_of_repeats has to be at least 1".to_string()
+        })?;
+        check(
+            self.min_temperature.is_finite() && self.min_temperature > 0.0,
+            || {
+                format!(
+                    "min_temperature has to be positive, got {}",
+                    self.min_temperature
+                )
+            },
+        )?;
+        check(
+            self.max_temperature.is_finite() && self.max_temperature >= self.min_temperature,
+            || {
+                format!(
+                    "max_temperature has to be at least min_temperature ({}), got {}",
+                    self.min_temperature, self.max_temperature
+                )
+            },
+        )?;
+        check_probability("probability_of_shuffle", self.probability_of_shuffle)?;
+        check_probability("probability_of_heuristic", self.probability_of_heuristic)?;
+        check_probability("swap_states_probability", self.swap_states_probability)?;
+        for (name, value) in [
+            ("temp_beta_a", self.temp_beta_a),
+            ("temp_beta_b", self.temp_beta_b),
+        ] {
+            check(value.is_finite() && value > 0.0, || {
+                format!(
+                    "{} is a Beta shape parameter and has to be positive, got {}",
+                    name, value
+                )
+            })?;
+        }
+        check(
+            self.max_length_percent_of_cycle > 0.0 && self.max_length_percent_of_cycle <= 0.5,
+            || {
+                format!(
+                    "max_length_percent_of_cycle has to be in (0, 0.5], got {}",
+                    self.max_length_percent_of_cycle
+                )
+            },
+        )?;
+        check(self.closeness.is_finite() && self.closeness > 0.0, || {
+            format!("closeness has to be positive, got {}", self.closeness)
+        })?;
+        check(self.cooling_rate > 0.0 && self.cooling_rate <= 1.0, || {
+            format!(
+                "cooling_rate has to be in (0, 1], got {}",
+                self.cooling_rate
+            )
+        })?;
+        check(
+            self.heuristic_keep_fraction > 0.0 && self.heuristic_keep_fraction <= 1.0,
+            || {
+                format!(
+                    "heuristic_keep_fraction has to be in (0, 1], got {}",
+                    self.heuristic_keep_fraction
+                )
+            },
+        )?;
+        check(self.number_of_neighbours >= 1, || {
+            "number_of_neighbours has to be at least 1".to_string()
+        })?;
+        check(
+            self.crossover_period == 0 || !self.crossovers.is_empty(),
+            || "crossovers cannot be empty when crossover_period is set".to_string(),
+        )?;
+        check(
+            self.penalty_weight.is_finite() && self.penalty_weight > 0.0,
+            || {
+                format!(
+                    "penalty_weight has to be positive, got {}",
+                    self.penalty_weight
+                )
+            },
+        )?;
+        check(
+            self.penalty_adaptation.is_finite() && self.penalty_adaptation >= 0.0,
+            || {
+                format!(
+                    "penalty_adaptation cannot be negative, got {}",
+                    self.penalty_adaptation
+                )
+            },
+        )?;
+        check_probability("probability_of_choice", self.probability_of_choice)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_non_square() {
+        let result = DistanceMatrix::try_new(vec![vec![0.0, 1.0], vec![1.0]]);
+        assert!(matches!(result, Err(ValidationError::Matrix(_))));
+    }
+
+    #[test]
+    fn test_rejects_bad_entries() {
+        let matrix = |entry: f64| {
+            DistanceMatrix::new(vec![
+                vec![f64::INFINITY, 1.0, 2.0],
+                vec![1.0, 0.0, entry],
+                vec![2.0, 1.0, 0.0],
+            ])
+        };
+        assert!(matrix(3.0).validate().is_ok());
+        assert!(matrix(f64::NAN).validate().is_err());
+        assert!(matrix(-1.0).validate().is_err());
+        assert!(matrix(f64::INFINITY).validate().is_err());
+        let small = DistanceMatrix::new(vec![vec![0, 1], vec![1, 0]]);
+        assert!(small.validate().is_err());
+    }
+
+    #[test]
+    fn test_rejects_tours_overflowing_the_cost_type() {
+        let size = 10;
+        let large = i32::MAX / 12;
+        let narrow = DistanceMatrix::new(vec![vec![large; size]; size]);
+        assert!(matches!(narrow.validate(), Err(ValidationError::Matrix(_))));
+        let wide = DistanceMatrix::new(vec![vec![i64::from(large); size]; size]);
+        assert!(wide.validate().is_ok());
+        let fitting = DistanceMatrix::new(vec![vec![i32::MAX / 20; size]; size]);
+        assert!(fitting.validate().is_ok());
+    }
+}
@@ -23,7 +23,7 @@ def set_parameters(exec_time: float) -> dict:
         "b": 1,
         "duration_of_execution_in_seconds": exec_time - 0.1,
         "k": 20,
-        "max_length_percent_of_cycle": 0.3,  # max 0.3 more will result in bugs
+        "max_length_percent_of_cycle": 0.3,  # at most 0.5
         "swap_states_probability": 0.1,
         "closeness": 1.5,
         "cooling_rate": 0.95,
@@ -117,8 +117,9 @@ def run_rust_for_one(problem_name: str) -> tuple[list[float], float]:
         time_s=exec_time,
         number_of_threads=threads - 1,
     )
-    solution, solution_length = result
+    solution, solution_length = result["solution"], result["cost"]
     print(f"Our best solution: {solution}\nOur best solution length: {solution_length}")
+    print(f"Gap to the assignment lower bound: {result['gap']:.2f}%")
     print(
         f"Rust length: {solution_length}, python validation: {cycle_length(solution, distance_matrix)}"
     )